@@ -0,0 +1,47 @@
+use crate::{
+    color, color::Color, hittable::Hittable, integrator::Integrator, onb::Onb, ray::Ray,
+    vector3::Vec3,
+};
+
+/// Integrator that shows how occluded the surfaces hit by camera rays are.
+/// From each hit point, `samples` rays are cast in the hemisphere around the
+/// surface's normal, and the ones that hit something closer than
+/// `max_distance` count as occluded. Unoccluded surfaces are white, and rays
+/// that don't hit anything are white as well.
+pub struct AmbientOcclusion {
+    samples: u32,
+    max_distance: f64,
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: u32, max_distance: f64) -> AmbientOcclusion {
+        AmbientOcclusion {
+            samples,
+            max_distance,
+        }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color {
+        let hit = match world.hit(ray, &(0.001..f64::INFINITY)) {
+            Some(hit) => hit,
+            None => return color!(1.0, 1.0, 1.0),
+        };
+
+        // Since the directions are cosine-weighted, the fraction of
+        // unoccluded rays already is the cosine-weighted visibility
+        let basis = Onb::from_w(hit.normal);
+        let occlusion_range = 0.001..self.max_distance;
+        let unoccluded = (0..self.samples)
+            .map(|_| basis.local_to_world(Vec3::random_cosine_direction()))
+            .filter(|&direction| {
+                let occlusion_ray = Ray::new(hit.point, direction);
+                world.hit(&occlusion_ray, &occlusion_range).is_none()
+            })
+            .count();
+
+        let visibility = unoccluded as f64 / self.samples.max(1) as f64;
+        color!(visibility, visibility, visibility)
+    }
+}
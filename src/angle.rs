@@ -0,0 +1,212 @@
+use derive_more::*;
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::rtweekend::PI;
+
+/// Angle's amplitude in degrees
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, From, Add, Sub, Mul, Div, Neg)]
+pub struct Degrees(f64);
+
+impl Degrees {
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+
+    pub fn sin(self) -> f64 {
+        Radians::from(self).sin()
+    }
+
+    pub fn cos(self) -> f64 {
+        Radians::from(self).cos()
+    }
+
+    pub fn tan(self) -> f64 {
+        Radians::from(self).tan()
+    }
+
+    /// Return the same angle in the range [0.0, 360.0)
+    pub fn normalized(self) -> Degrees {
+        Degrees(normalize(self.0, 360.0))
+    }
+}
+
+impl From<Radians> for Degrees {
+    /// Convert from radians to degrees
+    fn from(radians: Radians) -> Self {
+        Degrees(radians.0 * 180.0 / PI)
+    }
+}
+
+/// Angle's amplitude in radians
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, From, Add, Sub, Mul, Div, Neg)]
+pub struct Radians(f64);
+
+impl Radians {
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+
+    pub fn sin(self) -> f64 {
+        self.0.sin()
+    }
+
+    pub fn cos(self) -> f64 {
+        self.0.cos()
+    }
+
+    pub fn tan(self) -> f64 {
+        self.0.tan()
+    }
+
+    /// Return the same angle in the range [0.0, 2π)
+    pub fn normalized(self) -> Radians {
+        Radians(normalize(self.0, 2.0 * PI))
+    }
+}
+
+impl From<Degrees> for Radians {
+    /// Convert from degrees to radians
+    fn from(degrees: Degrees) -> Self {
+        Radians(degrees.0 * PI / 180.0)
+    }
+}
+
+/// Return `angle` in the range [0.0, `full_turn`)
+fn normalize(angle: f64, full_turn: f64) -> f64 {
+    let angle = angle.rem_euclid(full_turn);
+    // `rem_euclid` can round small negative angles up to a full turn
+    if angle >= full_turn {
+        0.0
+    } else {
+        angle
+    }
+}
+
+/// Error returned when parsing an angle from a string fails
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseAngleError(String);
+
+impl fmt::Display for ParseAngleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid angle '{}', expected a number followed by 'deg' or 'rad'",
+            self.0
+        )
+    }
+}
+
+impl Error for ParseAngleError {}
+
+/// Parse an angle like "45deg", "45°" or "0.7rad". Numbers without a unit
+/// are in the unit of `T`.
+fn parse_angle<T>(text: &str) -> Result<T, ParseAngleError>
+where
+    T: From<Degrees> + From<Radians> + From<f64>,
+{
+    let trimmed = text.trim();
+    let parse = |number: &str| {
+        number
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| ParseAngleError(text.to_string()))
+    };
+    if let Some(number) = trimmed
+        .strip_suffix("deg")
+        .or_else(|| trimmed.strip_suffix('°'))
+    {
+        Ok(Degrees(parse(number)?).into())
+    } else if let Some(number) = trimmed.strip_suffix("rad") {
+        Ok(Radians(parse(number)?).into())
+    } else {
+        Ok(parse(trimmed)?.into())
+    }
+}
+
+impl FromStr for Degrees {
+    type Err = ParseAngleError;
+
+    /// Parse an angle like "45deg" or "0.7rad", or "45" for 45 degrees
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse_angle(text)
+    }
+}
+
+impl FromStr for Radians {
+    type Err = ParseAngleError;
+
+    /// Parse an angle like "45deg" or "0.7rad", or "0.7" for 0.7 radians
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse_angle(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Angles from tiny ones to many turns, in either direction
+    fn any_angle() -> impl Strategy<Value = f64> {
+        prop_oneof![-1e5..1e5, -1e-10..1e-10, -1e-300..0.0]
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() <= 1e-12 * a.abs().max(1.0), "{a} != {b}");
+    }
+
+    #[test]
+    fn radians_to_degrees() {
+        // The conversion used to compute PI / (180 * radians)
+        assert_close(Degrees::from(Radians(PI)).as_f64(), 180.0);
+        assert_close(Degrees::from(Radians(PI / 2.0)).as_f64(), 90.0);
+        assert_close(Degrees::from(Radians(1.0)).as_f64(), 180.0 / PI);
+        assert_close(Radians::from(Degrees(90.0)).as_f64(), PI / 2.0);
+    }
+
+    #[test]
+    fn tiny_negative_angles_are_normalized_below_a_turn() {
+        // `rem_euclid` rounds these up to a full turn
+        for angle in [-f64::MIN_POSITIVE, -1e-300, -1e-17, -f64::EPSILON] {
+            assert_eq!(Degrees(angle).normalized(), Degrees(0.0));
+            assert_eq!(Radians(angle).normalized(), Radians(0.0));
+        }
+    }
+
+    #[test]
+    fn invalid_angles_are_errors() {
+        for text in ["", "deg", "45 degrees", "rad45", "forty-five°"] {
+            let error = text.parse::<Degrees>().unwrap_err();
+            assert_eq!(error, ParseAngleError(text.to_string()));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn conversions_round_trip(angle in any_angle()) {
+            assert_close(Degrees::from(Radians::from(Degrees(angle))).as_f64(), angle);
+            assert_close(Radians::from(Degrees::from(Radians(angle))).as_f64(), angle);
+        }
+
+        #[test]
+        fn normalized_angles_are_less_than_a_turn(angle in any_angle()) {
+            let degrees = Degrees(angle).normalized().as_f64();
+            prop_assert!((0.0..360.0).contains(&degrees), "{} -> {}", angle, degrees);
+            let radians = Radians(angle).normalized().as_f64();
+            prop_assert!((0.0..2.0 * PI).contains(&radians), "{} -> {}", angle, radians);
+        }
+
+        #[test]
+        fn parsed_angles_round_trip(angle in any_angle()) {
+            prop_assert_eq!(format!("{angle}deg").parse(), Ok(Degrees(angle)));
+            prop_assert_eq!(format!("{angle}°").parse(), Ok(Degrees(angle)));
+            prop_assert_eq!(format!(" {angle} rad ").parse(), Ok(Radians(angle)));
+            prop_assert_eq!(format!("{angle}").parse(), Ok(Degrees(angle)));
+            prop_assert_eq!(format!("{angle}").parse(), Ok(Radians(angle)));
+
+            // Angles in the other unit are converted
+            let degrees: Degrees = format!("{angle}rad").parse().unwrap();
+            assert_close(degrees.as_f64(), Degrees::from(Radians(angle)).as_f64());
+        }
+    }
+}
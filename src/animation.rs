@@ -0,0 +1,303 @@
+use std::{
+    fs, io,
+    ops::{Add, Mul, Range, Sub},
+    path::PathBuf,
+};
+
+use crate::{
+    angle::Degrees,
+    camera::{Camera, CameraBuilder},
+    hittable_list::HittableList,
+    integrator::Integrator,
+    render,
+    vector3::Vec3,
+    write_png,
+};
+
+/// How a `CameraPath` goes from one keyframe to the next
+#[derive(Clone, Copy)]
+pub enum Interpolation {
+    /// Straight lines between keyframes, with sudden changes of speed and
+    /// direction at each keyframe
+    Linear,
+    /// Catmull-Rom splines, which go smoothly through every keyframe
+    CatmullRom,
+}
+
+/// Position, target and field of view of a camera at some time
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    time: f64,
+    look_from: Vec3,
+    look_at: Vec3,
+    vertical_fov: Degrees,
+}
+
+impl Keyframe {
+    pub fn new(time: f64, look_from: Vec3, look_at: Vec3, vertical_fov: Degrees) -> Keyframe {
+        Keyframe {
+            time,
+            look_from,
+            look_at,
+            vertical_fov,
+        }
+    }
+}
+
+/// Frames of an animation, taken at evenly spaced times starting with the
+/// beginning of a time range, and saved in a directory as `frame_0001.png`,
+/// `frame_0002.png` and so on. Since the end of the range isn't included, a
+/// path that loops renders a video that loops too.
+pub struct FrameSequence {
+    times: Range<f64>,
+    frame_count: usize,
+    directory: PathBuf,
+}
+
+impl FrameSequence {
+    pub fn new(
+        times: Range<f64>,
+        frame_count: usize,
+        directory: impl Into<PathBuf>,
+    ) -> FrameSequence {
+        FrameSequence {
+            times,
+            frame_count,
+            directory: directory.into(),
+        }
+    }
+
+    /// Return the time of each frame, and the path of the file where it's
+    /// saved
+    fn frames(&self) -> impl Iterator<Item = (f64, PathBuf)> + '_ {
+        let Range { start, end } = self.times;
+        (0..self.frame_count).map(move |frame| {
+            let time = start + (end - start) * frame as f64 / self.frame_count as f64;
+            let path = self.directory.join(format!("frame_{:04}.png", frame + 1));
+            (time, path)
+        })
+    }
+}
+
+/// Path followed by a camera over time, given by some keyframes. Before the
+/// first keyframe and after the last one, the camera stays still.
+pub struct CameraPath {
+    builder: CameraBuilder,
+    keyframes: Vec<Keyframe>,
+    interpolation: Interpolation,
+}
+
+impl CameraPath {
+    /// Create a path without keyframes for cameras built by `builder`,
+    /// which gives every setting of the camera except the ones of the
+    /// keyframes
+    pub fn new(builder: CameraBuilder) -> CameraPath {
+        CameraPath {
+            builder,
+            keyframes: vec![],
+            interpolation: Interpolation::CatmullRom,
+        }
+    }
+
+    /// Add a keyframe to the path, which can be given in any order
+    pub fn with_keyframe(mut self, keyframe: Keyframe) -> CameraPath {
+        let idx = self
+            .keyframes
+            .partition_point(|other| other.time <= keyframe.time);
+        self.keyframes.insert(idx, keyframe);
+        self
+    }
+
+    /// Interpolation between keyframes, which is `CatmullRom` by default
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> CameraPath {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Return the camera at time `time`, or the camera of the builder if
+    /// there are no keyframes
+    pub fn camera_at(&self, time: f64) -> Camera {
+        let keyframes = &self.keyframes;
+        if keyframes.is_empty() {
+            return self.builder.build();
+        }
+
+        // Keyframes before and after `time`, and the ones around them
+        let next = keyframes
+            .partition_point(|keyframe| keyframe.time <= time)
+            .min(keyframes.len() - 1);
+        let prev = next.saturating_sub(1);
+        let around = [
+            keyframes[prev.saturating_sub(1)],
+            keyframes[prev],
+            keyframes[next],
+            keyframes[(next + 1).min(keyframes.len() - 1)],
+        ];
+
+        let duration = around[2].time - around[1].time;
+        let t = if duration > 0.0 {
+            ((time - around[1].time) / duration).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let times = around.map(|keyframe| keyframe.time);
+        let look_from = self.interpolate(around.map(|keyframe| keyframe.look_from), times, t);
+        let look_at = self.interpolate(around.map(|keyframe| keyframe.look_at), times, t);
+        let fov = self.interpolate(
+            around.map(|keyframe| keyframe.vertical_fov.as_f64()),
+            times,
+            t,
+        );
+
+        self.builder
+            .clone()
+            .look_from(look_from)
+            .look_at(look_at)
+            .vertical_fov(fov.into())
+            .build()
+    }
+
+    /// Interpolate between the second and third of four values of
+    /// consecutive keyframes at the given times, where `t` goes from 0.0 at
+    /// the second keyframe to 1.0 at the third one
+    fn interpolate<T>(&self, points: [T; 4], times: [f64; 4], t: f64) -> T
+    where
+        T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+    {
+        match self.interpolation {
+            Interpolation::Linear => points[1] + (points[2] - points[1]) * t,
+            Interpolation::CatmullRom => catmull_rom(points, times, t),
+        }
+    }
+
+    /// Render the frames of `frames` with `world` as seen from the cameras
+    /// of the path, creating their directory if needed. The same world is
+    /// used for every frame, and the integrator of each frame is created by
+    /// `integrator` with the world and the camera of that frame.
+    pub fn render_frames(
+        &self,
+        world: &HittableList,
+        frames: &FrameSequence,
+        integrator: impl for<'a> Fn(&'a HittableList, &'a Camera) -> Box<dyn Integrator + 'a>,
+        image_width: usize,
+        samples_per_pixel: u32,
+    ) -> io::Result<()> {
+        fs::create_dir_all(&frames.directory)?;
+
+        for (time, path) in frames.frames() {
+            let camera = self.camera_at(time);
+            let pixels = render(
+                world,
+                &camera,
+                integrator(world, &camera).as_ref(),
+                image_width,
+                samples_per_pixel,
+            );
+            write_png(&pixels, image_width, &path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Return the point at `t` of the Catmull-Rom spline between `p1` and `p2`,
+/// whose tangents there are given by the points `p0` before and `p3` after
+/// them. The tangents are scaled by the times of the points, so that the
+/// speed doesn't jump at `p1` and `p2` when keyframes are unevenly spaced.
+fn catmull_rom<T>([p0, p1, p2, p3]: [T; 4], [time0, time1, time2, time3]: [f64; 4], t: f64) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    // Tangents at `p1` and `p2` over the time from `p1` to `p2`, which are
+    // the ones of the uniform spline when keyframes are evenly spaced
+    let duration = time2 - time1;
+    let tangent = |before: T, after: T, interval: f64| {
+        let scale = if interval > 0.0 {
+            duration / interval
+        } else {
+            0.0
+        };
+        (after - before) * scale
+    };
+    let m1 = tangent(p0, p2, time2 - time0);
+    let m2 = tangent(p1, p3, time3 - time1);
+
+    // Cubic Hermite spline
+    let (t2, t3) = (t * t, t * t * t);
+    p1 * (2.0 * t3 - 3.0 * t2 + 1.0)
+        + m1 * (t3 - 2.0 * t2 + t)
+        + p2 * (3.0 * t2 - 2.0 * t3)
+        + m2 * (t3 - t2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3;
+
+    /// Return the position of the camera of `path` at `time`
+    fn position_at(path: &CameraPath, time: f64) -> Vec3 {
+        path.camera_at(time).get_ray(0.5, 0.5).unwrap().origin
+    }
+
+    /// Path through points at uneven times
+    fn uneven_path() -> CameraPath {
+        let keyframe =
+            |time, look_from| Keyframe::new(time, look_from, vec3!(0.0, 0.0, -10.0), 40.0.into());
+        CameraPath::new(Camera::builder())
+            .with_keyframe(keyframe(0.0, vec3!(0.0, 0.0, 0.0)))
+            .with_keyframe(keyframe(3.0, vec3!(1.0, 2.0, 0.0)))
+            .with_keyframe(keyframe(1.0, vec3!(1.0, 0.0, 0.0)))
+            .with_keyframe(keyframe(3.5, vec3!(0.0, 3.0, 1.0)))
+    }
+
+    #[test]
+    fn path_goes_through_keyframes() {
+        let path = uneven_path();
+        for keyframe in &path.keyframes {
+            let position = position_at(&path, keyframe.time);
+            assert!((position - keyframe.look_from).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn speed_is_continuous_at_keyframes() {
+        // Keyframes at 1.0 and 3.0 are between intervals of different length
+        let path = uneven_path();
+        let dt = 1e-6;
+        for time in [1.0, 3.0] {
+            let before = (position_at(&path, time) - position_at(&path, time - dt)) / dt;
+            let after = (position_at(&path, time + dt) - position_at(&path, time)) / dt;
+            assert!((before - after).length() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn linear_interpolation_gives_the_midpoint() {
+        let look_at = vec3!(0.0, 0.0, -10.0);
+        let path = CameraPath::new(Camera::builder())
+            .with_keyframe(Keyframe::new(
+                1.0,
+                vec3!(0.0, 0.0, 0.0),
+                look_at,
+                40.0.into(),
+            ))
+            .with_keyframe(Keyframe::new(
+                3.0,
+                vec3!(2.0, 4.0, 6.0),
+                look_at,
+                40.0.into(),
+            ))
+            .with_interpolation(Interpolation::Linear);
+        let position = position_at(&path, 2.0);
+        assert!((position - vec3!(1.0, 2.0, 3.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn frames_are_numbered_from_one() {
+        let frames = FrameSequence::new(0.0..1.0, 4, "frames");
+        let frames: Vec<_> = frames.frames().collect();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0], (0.0, PathBuf::from("frames/frame_0001.png")));
+        assert_eq!(frames[3], (0.75, PathBuf::from("frames/frame_0004.png")));
+    }
+}
@@ -0,0 +1,233 @@
+use std::rc::Rc;
+
+use crate::{
+    angle::{Degrees, Radians},
+    rtweekend::{random_num, PI},
+    texture::Texture,
+    vec3,
+    vector3::Vec3,
+};
+
+/// Shape of the aperture of a camera's lens, which is the shape that
+/// out-of-focus highlights (bokeh) take
+#[derive(Clone)]
+pub enum ApertureShape {
+    /// Round aperture
+    Circle,
+    /// Regular polygon made by the blades of a diaphragm, rotated by the
+    /// given angle
+    Polygon { blades: u32, rotation: Degrees },
+    /// Aperture given by a grayscale image covering the lens, whose
+    /// luminance is how much light goes through each point of it. The lens
+    /// is round, so the corners of the image outside of it are cut off.
+    Mask(Rc<dyn Texture>),
+}
+
+/// Number of cells along each side of the grid in which mask apertures are
+/// sampled
+const MASK_RESOLUTION: usize = 128;
+
+/// Number of points along each side of a cell of a mask aperture used to
+/// find how much of the cell is inside the lens
+const MASK_CELL_POINTS: usize = 8;
+
+/// Aperture ready to be sampled. Points of the aperture are given in the
+/// square [-1.0, 1.0]², which is scaled to the size of the lens.
+pub(crate) enum Aperture {
+    Circle,
+    Polygon {
+        /// Corners of the polygon, in counter-clockwise order
+        corners: Vec<Vec3>,
+        area: f64,
+    },
+    Mask {
+        /// Luminance of each cell of the grid, row by row starting with the
+        /// bottom one
+        cells: Vec<f64>,
+        /// Cumulative sum of the light that goes through each cell, which is
+        /// its luminance times the fraction of it inside the lens
+        cdf: Vec<f64>,
+    },
+}
+
+impl Aperture {
+    pub(crate) fn new(shape: &ApertureShape) -> Aperture {
+        match shape {
+            ApertureShape::Circle => Aperture::Circle,
+            ApertureShape::Polygon { blades, rotation } => {
+                let blades = (*blades).max(3);
+                let rotation = Radians::from(*rotation);
+                let corners = (0..blades)
+                    .map(|i| {
+                        let angle = rotation + Radians::from(2.0 * PI * i as f64 / blades as f64);
+                        vec3!(angle.cos(), angle.sin(), 0.0)
+                    })
+                    .collect();
+                let area = 0.5 * blades as f64 * (2.0 * PI / blades as f64).sin();
+                Aperture::Polygon { corners, area }
+            }
+            ApertureShape::Mask(texture) => {
+                let cell_center = |i: usize| (i as f64 + 0.5) / MASK_RESOLUTION as f64;
+                let cells: Vec<f64> = (0..MASK_RESOLUTION * MASK_RESOLUTION)
+                    .map(|i| {
+                        let (u, v) = (
+                            cell_center(i % MASK_RESOLUTION),
+                            cell_center(i / MASK_RESOLUTION),
+                        );
+                        let point = vec3!(2.0 * u - 1.0, 2.0 * v - 1.0, 0.0);
+                        texture.value(u, v, point).luminance().clamp(0.0, 1.0)
+                    })
+                    .collect();
+                let cdf = cells
+                    .iter()
+                    .enumerate()
+                    .scan(0.0, |sum, (i, cell)| {
+                        *sum += cell * mask_cell_coverage(i);
+                        Some(*sum)
+                    })
+                    .collect();
+                Aperture::Mask { cells, cdf }
+            }
+        }
+    }
+
+    /// Return a random point of the aperture, chosen with a probability
+    /// proportional to how much light goes through it
+    pub(crate) fn sample(&self) -> Vec3 {
+        match self {
+            Aperture::Circle => Vec3::random_in_unit_disc(),
+            Aperture::Polygon { corners, .. } => {
+                // Choose one of the triangles between the center and two
+                // consecutive corners, and then a point inside of it
+                let i = ((random_num() * corners.len() as f64) as usize).min(corners.len() - 1);
+                let (a, b) = (corners[i], corners[(i + 1) % corners.len()]);
+                let (mut s, mut t) = (random_num(), random_num());
+                if s + t > 1.0 {
+                    (s, t) = (1.0 - s, 1.0 - t);
+                }
+                s * a + t * b
+            }
+            Aperture::Mask { cdf, .. } => {
+                let total = cdf.last().copied().unwrap_or(0.0);
+                if total <= 0.0 {
+                    return Vec3::zero();
+                }
+                let target = random_num() * total;
+                let i = cdf.partition_point(|&sum| sum <= target).min(cdf.len() - 1);
+
+                // Choose a point of the cell inside the lens. The cell was
+                // chosen, so some of it is inside the lens.
+                let point_in_cell = || mask_cell_point(i, random_num(), random_num());
+                (0..64)
+                    .map(|_| point_in_cell())
+                    .find(|point| in_unit_disc(*point))
+                    .unwrap_or_else(|| {
+                        let point = point_in_cell();
+                        point / point.length().max(1.0)
+                    })
+            }
+        }
+    }
+
+    /// Return the probability density (per unit area of the square
+    /// [-1.0, 1.0]²) with which `sample` returns `point`
+    pub(crate) fn pdf(&self, point: Vec3) -> f64 {
+        match self {
+            Aperture::Circle => {
+                if in_unit_disc(point) {
+                    1.0 / PI
+                } else {
+                    0.0
+                }
+            }
+            Aperture::Polygon { corners, area } => {
+                // The point is inside if it's on the left of every edge
+                let inside = corners
+                    .iter()
+                    .zip(corners.iter().cycle().skip(1))
+                    .all(|(a, b)| {
+                        (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x) >= 0.0
+                    });
+                if inside {
+                    1.0 / area
+                } else {
+                    0.0
+                }
+            }
+            Aperture::Mask { cells, cdf } => {
+                let total = cdf.last().copied().unwrap_or(0.0);
+                let cell = |coordinate: f64| {
+                    let cell = ((coordinate + 1.0) / 2.0 * MASK_RESOLUTION as f64).floor();
+                    (0.0..MASK_RESOLUTION as f64)
+                        .contains(&cell)
+                        .then_some(cell as usize)
+                };
+                match (cell(point.x), cell(point.y)) {
+                    (Some(x), Some(y))
+                        if total > 0.0
+                            && in_unit_disc(point)
+                            && mask_cell_coverage(y * MASK_RESOLUTION + x) > 0.0 =>
+                    {
+                        let cell_area = (2.0 / MASK_RESOLUTION as f64).powi(2);
+                        cells[y * MASK_RESOLUTION + x] / (total * cell_area)
+                    }
+                    _ => 0.0,
+                }
+            }
+        }
+    }
+}
+
+/// Return whether `point` is inside the unit disc, which is the lens
+fn in_unit_disc(point: Vec3) -> bool {
+    point.x * point.x + point.y * point.y <= 1.0
+}
+
+/// Return the point of the `i`-th cell of a mask aperture at the fractions
+/// `s` and `t` of its width and height
+fn mask_cell_point(i: usize, s: f64, t: f64) -> Vec3 {
+    let cell_size = 2.0 / MASK_RESOLUTION as f64;
+    let (x, y) = (i % MASK_RESOLUTION, i / MASK_RESOLUTION);
+    vec3!(
+        -1.0 + (x as f64 + s) * cell_size,
+        -1.0 + (y as f64 + t) * cell_size,
+        0.0
+    )
+}
+
+/// Return the fraction of the `i`-th cell of a mask aperture that is inside
+/// the lens
+fn mask_cell_coverage(i: usize) -> f64 {
+    let offset = |j: usize| (j as f64 + 0.5) / MASK_CELL_POINTS as f64;
+    let inside = (0..MASK_CELL_POINTS * MASK_CELL_POINTS)
+        .filter(|j| {
+            let point = mask_cell_point(
+                i,
+                offset(j % MASK_CELL_POINTS),
+                offset(j / MASK_CELL_POINTS),
+            );
+            in_unit_disc(point)
+        })
+        .count();
+    inside as f64 / (MASK_CELL_POINTS * MASK_CELL_POINTS) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color, color::Color, texture::SolidColor};
+
+    #[test]
+    fn masks_are_cut_off_by_the_lens() {
+        let white = Rc::new(SolidColor::new(color!(1.0, 1.0, 1.0)));
+        let aperture = Aperture::new(&ApertureShape::Mask(white));
+        for _ in 0..10_000 {
+            assert!(in_unit_disc(aperture.sample()));
+        }
+        assert_eq!(aperture.pdf(vec3!(0.95, 0.95, 0.0)), 0.0);
+
+        // A white mask lets light through the whole lens, like a circle
+        let pdf = aperture.pdf(vec3!(0.1, -0.3, 0.0));
+        assert!((pdf - 1.0 / PI).abs() < 0.01 / PI);
+    }
+}
@@ -0,0 +1,524 @@
+use crate::{
+    camera::Camera,
+    color,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    integrator::{background, Integrator, Splat},
+    lights::Lights,
+    onb::Onb,
+    ray::Ray,
+    rtweekend::PI,
+    vector3::Vec3,
+};
+
+const HIT_RANGE: std::ops::Range<f64> = 0.001..f64::INFINITY;
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind {
+    /// Point of the camera's lens
+    Camera,
+    /// Point of the surface of a light, where a light subpath starts
+    Light,
+    /// Point where a subpath hit a surface
+    Surface,
+}
+
+/// Point of a camera or light subpath
+#[derive(Clone)]
+struct Vertex {
+    kind: VertexKind,
+    point: Vec3,
+    /// Normal of the surface, or the view direction for the camera
+    normal: Vec3,
+    /// Unit vector pointing to the previous vertex of the subpath
+    wo: Vec3,
+    /// Hit record of the surface. Only `None` for the camera.
+    hit: Option<HitRecord>,
+    /// Product of the attenuations of the subpath up to this vertex, divided
+    /// by the probability of sampling it
+    beta: Color,
+    /// Whether the material of the vertex scatters rays in specific directions
+    /// only, so that it can't be connected to other vertices
+    delta: bool,
+    /// Probability density (per unit area) of sampling this vertex from the
+    /// previous one of its subpath
+    pdf_fwd: f64,
+    /// Probability density (per unit area) of sampling this vertex from the
+    /// next one of its subpath, if the subpath had been sampled in the opposite
+    /// direction
+    pdf_rev: f64,
+}
+
+impl Vertex {
+    fn camera(camera: &Camera, point: Vec3, beta: Color) -> Vertex {
+        Vertex {
+            kind: VertexKind::Camera,
+            point,
+            normal: camera.view_direction(),
+            wo: camera.view_direction(),
+            hit: None,
+            beta,
+            // Cameras that can't be connected to are like delta vertices,
+            // so that no path is expected to be sampled by connecting to them
+            delta: !camera.is_connectible(),
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn light(hit: HitRecord, beta: Color, pdf_fwd: f64) -> Vertex {
+        Vertex {
+            kind: VertexKind::Light,
+            point: hit.point,
+            normal: hit.normal,
+            wo: hit.normal,
+            hit: Some(hit),
+            beta,
+            delta: false,
+            pdf_fwd,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn surface(hit: HitRecord, wo: Vec3, beta: Color) -> Vertex {
+        Vertex {
+            kind: VertexKind::Surface,
+            point: hit.point,
+            normal: hit.normal,
+            wo,
+            hit: Some(hit),
+            beta,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn is_on_surface(&self) -> bool {
+        self.kind != VertexKind::Camera
+    }
+
+    fn is_connectible(&self) -> bool {
+        match self.kind {
+            VertexKind::Camera | VertexKind::Light => true,
+            VertexKind::Surface => !self.delta,
+        }
+    }
+
+    /// Return true if this vertex is on the surface of a light
+    fn is_light(&self) -> bool {
+        match (&self.kind, &self.hit) {
+            (VertexKind::Light, _) => true,
+            (VertexKind::Surface, Some(hit)) => hit.material.emits_light(),
+            _ => false,
+        }
+    }
+
+    /// Return the light emitted from this vertex towards the previous one
+    fn emitted(&self) -> Color {
+        match &self.hit {
+            Some(hit) if self.is_light() => hit.material.emitted(hit),
+            _ => color!(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Return the BSDF of this vertex for light that goes from `next` to the
+    /// previous vertex of the subpath
+    fn bsdf(&self, next: &Vertex) -> Color {
+        let wi = (next.point - self.point).unit_vec();
+        self.hit
+            .as_ref()
+            .and_then(|hit| hit.material.eval(self.wo, wi, hit))
+            .unwrap_or(color!(0.0, 0.0, 0.0))
+    }
+
+    /// Convert a probability density per unit solid angle of sampling `next`
+    /// from this vertex into a density per unit area of `next`'s surface
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        let w = next.point - self.point;
+        let dist_squared = w.length_squared();
+        if dist_squared == 0.0 {
+            return 0.0;
+        }
+        let mut pdf = pdf / dist_squared;
+        if next.is_on_surface() {
+            pdf *= next.normal.dot(w / dist_squared.sqrt()).abs();
+        }
+        pdf
+    }
+
+    /// Return the probability density (per unit area) with which a light
+    /// subpath that starts at this vertex chooses `next` as its next vertex
+    fn pdf_light(&self, next: &Vertex) -> f64 {
+        let direction = (next.point - self.point).unit_vec();
+        // Lights emit in cosine-weighted directions
+        let pdf_dir = self.normal.dot(direction).abs() / PI;
+        self.convert_density(pdf_dir, next)
+    }
+}
+
+/// Bidirectional path tracer. For each camera ray, it traces a subpath from
+/// the camera and another one from a random point of a light, and then
+/// connects every vertex of one subpath with every vertex of the other one.
+/// The contributions of all those paths are weighted with multiple importance
+/// sampling, using the balance heuristic.
+///
+/// Light that reaches the camera without bouncing on any surface that emits
+/// light (like the background) can only be found by the camera subpath.
+pub struct BidirectionalPathTracer<'a> {
+    camera: &'a Camera,
+    lights: Lights<'a>,
+    max_depth: usize,
+}
+
+impl<'a> BidirectionalPathTracer<'a> {
+    /// Create a bidirectional path tracer for scenes rendered with `camera`,
+    /// and whose lights are the ones in `world`. Paths will have at most
+    /// `max_depth` bounces.
+    pub fn new(
+        world: &'a dyn Hittable,
+        camera: &'a Camera,
+        max_depth: usize,
+    ) -> BidirectionalPathTracer<'a> {
+        BidirectionalPathTracer {
+            camera,
+            lights: Lights::new(world),
+            max_depth,
+        }
+    }
+
+    /// Return the probability density (per unit area) of sampling `next` from
+    /// `vertex`, which was sampled from `prev`
+    fn pdf(&self, vertex: &Vertex, prev: Option<&Vertex>, next: &Vertex) -> f64 {
+        let direction = (next.point - vertex.point).unit_vec();
+        let pdf_dir = match vertex.kind {
+            VertexKind::Light => return vertex.pdf_light(next),
+            VertexKind::Camera => self
+                .camera
+                .direction_pdf(&Ray::new(vertex.point, direction)),
+            VertexKind::Surface => {
+                let wo = prev.map_or(vertex.wo, |prev| (prev.point - vertex.point).unit_vec());
+                let hit = vertex.hit.as_ref().unwrap();
+                hit.material.pdf(wo, direction, hit)
+            }
+        };
+        vertex.convert_density(pdf_dir, next)
+    }
+
+    /// Probability density (per unit area) of starting a light subpath at a
+    /// given point of a light
+    fn pdf_light_origin(&self) -> f64 {
+        self.lights.pdf_area()
+    }
+
+    /// Keep tracing `ray`, adding a vertex to `path` for every surface it hits,
+    /// until it is absorbed, escapes the scene or `max_vertices` are added.
+    /// `pdf_dir` is the density (per unit solid angle) with which the direction
+    /// of `ray` was sampled. Return the light from the background that reaches
+    /// the start of the subpath when it escapes the scene.
+    fn random_walk(
+        &self,
+        world: &dyn Hittable,
+        mut ray: Ray,
+        mut beta: Color,
+        mut pdf_dir: f64,
+        path: &mut Vec<Vertex>,
+        max_vertices: usize,
+    ) -> Color {
+        for _ in 0..max_vertices {
+            let hit = match world.hit(&ray, &HIT_RANGE) {
+                Some(hit) => hit,
+                None => return beta * background(&ray),
+            };
+
+            let prev = path.len() - 1;
+            let mut vertex = Vertex::surface(hit, -ray.direction, beta);
+            vertex.pdf_fwd = path[prev].convert_density(pdf_dir, &vertex);
+
+            let hit = vertex.hit.as_ref().unwrap();
+            let scattered = hit.material.scatter(&ray, hit);
+            let (scattered_ray, attenuation) = match scattered {
+                Some(scattered) => scattered,
+                None => {
+                    path.push(vertex);
+                    break;
+                }
+            };
+
+            let wo = vertex.wo;
+            let wi = scattered_ray.direction;
+            let pdf_rev = if hit.material.eval(wo, wi, hit).is_some() {
+                pdf_dir = hit.material.pdf(wo, wi, hit);
+                hit.material.pdf(wi, wo, hit)
+            } else {
+                vertex.delta = true;
+                pdf_dir = 0.0;
+                0.0
+            };
+            path[prev].pdf_rev = vertex.convert_density(pdf_rev, &path[prev]);
+
+            beta = beta * attenuation;
+            ray = scattered_ray;
+            path.push(vertex);
+        }
+        color!(0.0, 0.0, 0.0)
+    }
+
+    /// Trace a subpath that starts with a camera ray. Return its vertices and
+    /// the light from the background it found.
+    fn camera_subpath(&self, world: &dyn Hittable, ray: &Ray) -> (Vec<Vertex>, Color) {
+        let beta = color!(1.0, 1.0, 1.0);
+        let mut path = vec![Vertex::camera(self.camera, ray.origin, beta)];
+        let pdf_dir = self.camera.direction_pdf(ray);
+        let background =
+            self.random_walk(world, *ray, beta, pdf_dir, &mut path, self.max_depth + 1);
+        (path, background)
+    }
+
+    /// Trace a subpath that starts at a random point of a light
+    fn light_subpath(&self, world: &dyn Hittable) -> Vec<Vertex> {
+        let hit = match self.lights.sample() {
+            Some(hit) => hit,
+            None => return vec![],
+        };
+
+        let emitted = hit.material.emitted(&hit);
+        let direction = Onb::from_w(hit.normal).local_to_world(Vec3::random_cosine_direction());
+        let cos_theta = direction.dot(hit.normal);
+        let pdf_pos = self.pdf_light_origin();
+        let pdf_dir = cos_theta / PI;
+
+        let ray = Ray::new(hit.point, direction);
+        let beta = emitted * (cos_theta / (pdf_pos * pdf_dir));
+        let mut path = vec![Vertex::light(hit, emitted, pdf_pos)];
+        self.random_walk(world, ray, beta, pdf_dir, &mut path, self.max_depth);
+        path
+    }
+
+    /// Return true if nothing is in the way between `a` and `b`
+    fn visible(world: &dyn Hittable, a: &Vertex, b: &Vertex) -> bool {
+        let offset = b.point - a.point;
+        let dist = offset.length();
+        let ray = Ray::new(a.point, offset);
+        world
+            .hit(&ray, &(HIT_RANGE.start..dist - HIT_RANGE.start))
+            .is_none()
+    }
+
+    /// Connect the first `s` vertices of `light_path` with the first `t`
+    /// vertices of `camera_path`. Return the light carried by the resulting
+    /// path, already weighted, and the film coordinates it arrives at when
+    /// they are not the ones of the camera ray.
+    fn connect(
+        &self,
+        world: &dyn Hittable,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+    ) -> Option<(Color, Option<(f64, f64)>)> {
+        let mut sampled = None;
+        let mut film_coordinates = None;
+
+        let radiance = if s == 0 {
+            // The camera subpath hit a light by itself
+            let pt = &camera_path[t - 1];
+            pt.beta * pt.emitted()
+        } else if t == 1 {
+            // Connect a vertex of the light subpath to a point of the lens
+            let qs = &light_path[s - 1];
+            if !qs.is_connectible() {
+                return None;
+            }
+            let lens_point = self.camera.sample_lens();
+            let to_vertex = qs.point - lens_point;
+            let ray = Ray::new(lens_point, to_vertex);
+            let (film_s, film_t) = self.camera.film_coordinates(&ray)?;
+            let cos_lens = ray.direction.dot(self.camera.view_direction());
+            let pdf = to_vertex.length_squared() * self.camera.lens_pdf(lens_point) / cos_lens;
+            let importance = self.camera.importance(&ray) / pdf;
+
+            let camera_vertex = Vertex::camera(
+                self.camera,
+                lens_point,
+                color!(importance, importance, importance),
+            );
+            let cos_vertex = qs.normal.dot(ray.direction).abs();
+            let radiance = qs.beta * qs.bsdf(&camera_vertex) * camera_vertex.beta * cos_vertex;
+            if !BidirectionalPathTracer::visible(world, qs, &camera_vertex) {
+                return None;
+            }
+            film_coordinates = Some((film_s, film_t));
+            sampled = Some(camera_vertex);
+            radiance
+        } else if s == 1 {
+            // Connect a vertex of the camera subpath to a new point of a light
+            let pt = &camera_path[t - 1];
+            if !pt.is_connectible() {
+                return None;
+            }
+            let hit = self.lights.sample()?;
+            let to_light = hit.point - pt.point;
+            let dist_squared = to_light.length_squared();
+            let direction = to_light / dist_squared.sqrt();
+            let cos_light = -hit.normal.dot(direction);
+            if cos_light <= 0.0 {
+                return None;
+            }
+            let pdf = self.lights.pdf_area() * dist_squared / cos_light;
+            let emitted = hit.material.emitted(&hit);
+
+            let light_vertex = Vertex::light(hit, emitted / pdf, self.pdf_light_origin());
+            let cos_vertex = pt.normal.dot(direction).abs();
+            let radiance = pt.beta * pt.bsdf(&light_vertex) * light_vertex.beta * cos_vertex;
+            if !BidirectionalPathTracer::visible(world, pt, &light_vertex) {
+                return None;
+            }
+            sampled = Some(light_vertex);
+            radiance
+        } else {
+            // Connect a vertex of each subpath
+            let qs = &light_path[s - 1];
+            let pt = &camera_path[t - 1];
+            if !qs.is_connectible() || !pt.is_connectible() {
+                return None;
+            }
+            let offset = pt.point - qs.point;
+            let dist_squared = offset.length_squared();
+            let direction = offset / dist_squared.sqrt();
+            let geometry =
+                qs.normal.dot(direction).abs() * pt.normal.dot(direction).abs() / dist_squared;
+            let radiance = qs.beta * qs.bsdf(pt) * pt.bsdf(qs) * pt.beta * geometry;
+            if !BidirectionalPathTracer::visible(world, qs, pt) {
+                return None;
+            }
+            radiance
+        };
+
+        if radiance.is_black() {
+            return None;
+        }
+        let weight = self.mis_weight(light_path, camera_path, sampled.as_ref(), s, t);
+        Some((weight * radiance, film_coordinates))
+    }
+
+    /// Return the weight of the path made by connecting the first `s` vertices
+    /// of `light_path` and the first `t` vertices of `camera_path`, compared to
+    /// the other ways in which the same path could have been sampled.
+    /// `sampled` is the vertex that replaces the last one of the light subpath
+    /// when `s == 1`, or the last one of the camera subpath when `t == 1`.
+    fn mis_weight(
+        &self,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        sampled: Option<&Vertex>,
+        s: usize,
+        t: usize,
+    ) -> f64 {
+        if s + t == 2 {
+            return 1.0;
+        }
+
+        let pt = match (t, sampled) {
+            (1, Some(sampled)) => sampled,
+            _ => &camera_path[t - 1],
+        };
+        let qs = match (s, sampled) {
+            (0, _) => None,
+            (1, Some(sampled)) => Some(sampled),
+            _ => Some(&light_path[s - 1]),
+        };
+        let pt_minus = t.checked_sub(2).map(|i| &camera_path[i]);
+        let qs_minus = s.checked_sub(2).map(|i| &light_path[i]);
+
+        // Probability densities (forward and reverse) of every vertex, and
+        // whether it is a delta vertex
+        let densities = |v: &Vertex| (v.pdf_fwd, v.pdf_rev, v.delta);
+        let mut camera: Vec<_> = camera_path[..t].iter().map(densities).collect();
+        let mut light: Vec<_> = light_path[..s].iter().map(densities).collect();
+        camera[t - 1] = densities(pt);
+        if let Some(qs) = qs {
+            light[s - 1] = densities(qs);
+        }
+
+        // The vertices that are connected can't be delta vertices
+        camera[t - 1].2 = false;
+        if s > 0 {
+            light[s - 1].2 = false;
+        }
+
+        // Update the reverse densities of the vertices around the connection
+        camera[t - 1].1 = match qs {
+            Some(qs) => self.pdf(qs, qs_minus, pt),
+            None => self.pdf_light_origin(),
+        };
+        if let Some(pt_minus) = pt_minus {
+            camera[t - 2].1 = match qs {
+                Some(qs) => self.pdf(pt, Some(qs), pt_minus),
+                None => pt.pdf_light(pt_minus),
+            };
+        }
+        if let Some(qs) = qs {
+            light[s - 1].1 = self.pdf(pt, pt_minus, qs);
+        }
+        if let (Some(qs), Some(qs_minus)) = (qs, qs_minus) {
+            light[s - 2].1 = self.pdf(qs, Some(pt), qs_minus);
+        }
+
+        let remap0 = |pdf: f64| if pdf != 0.0 { pdf } else { 1.0 };
+        let mut sum_ratios = 0.0;
+
+        let mut ratio = 1.0;
+        for i in (1..t).rev() {
+            ratio *= remap0(camera[i].1) / remap0(camera[i].0);
+            if !camera[i].2 && !camera[i - 1].2 {
+                sum_ratios += ratio;
+            }
+        }
+
+        let mut ratio = 1.0;
+        for i in (0..s).rev() {
+            ratio *= remap0(light[i].1) / remap0(light[i].0);
+            let delta_light_vertex = i > 0 && light[i - 1].2;
+            if !light[i].2 && !delta_light_vertex {
+                sum_ratios += ratio;
+            }
+        }
+
+        1.0 / (1.0 + sum_ratios)
+    }
+}
+
+impl<'a> Integrator for BidirectionalPathTracer<'a> {
+    /// Return the radiance carried by `ray`. The light that the light subpaths
+    /// take to other points of the film is lost, so `create_image` uses
+    /// `radiance_and_splats` instead.
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color {
+        self.radiance_and_splats(ray, world, &mut vec![])
+    }
+
+    fn radiance_and_splats(
+        &self,
+        ray: &Ray,
+        world: &dyn Hittable,
+        splats: &mut Vec<Splat>,
+    ) -> Color {
+        let (camera_path, mut radiance) = self.camera_subpath(world, ray);
+        let light_path = self.light_subpath(world);
+
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                let depth = s + t;
+                if (s == 1 && t == 1) || depth < 2 || depth - 2 > self.max_depth {
+                    continue;
+                }
+                match self.connect(world, &light_path, &camera_path, s, t) {
+                    Some((color, Some((s, t)))) => splats.push(Splat { s, t, color }),
+                    Some((color, None)) => radiance += color,
+                    None => {}
+                }
+            }
+        }
+        radiance
+    }
+}
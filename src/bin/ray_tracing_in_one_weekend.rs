@@ -1,26 +1,168 @@
 use std::rc::Rc;
 
 use ray_tracing_in_one_weekend::{
-    color, create_image, random_num, random_num_in_range, vec3, Camera, Color, Dielectric,
-    HittableList, Lambertian, Metal, Sphere, Vec3,
+    color, create_image, debug_integrators, random_num, random_num_in_range, vec3,
+    AmbientOcclusion, BidirectionalPathTracer, Camera, CameraBuilder, CameraPath, Color, Degrees,
+    Dielectric, DiffuseLight, FrameSequence, HittableList, Integrator, Keyframe, Lambertian, Metal,
+    PathTracer, PhotonMapping, ProgressivePhotonMapping, SpectralPathTracer, Sphere, Vec3,
 };
 
+/// Scene rendered by the binary, and the camera that looks at it
+struct Scene {
+    world: HittableList,
+    look_from: Vec3,
+    look_at: Vec3,
+    vertical_fov: Degrees,
+    /// Settings of the camera other than its position, target and field of
+    /// view
+    camera: CameraBuilder,
+}
+
 fn main() {
-    let world = random_world();
-    let camera = Camera::builder()
-        .look_from(vec3!(13.0, 2.0, 3.0))
-        .look_at(Vec3::zero())
-        .vertical_fov(20.0.into())
-        .aspect_ratio(3.0 / 2.0)
-        .up_vector(vec3!(0.0, 1.0, 0.0))
-        .focus_distance(10.0)
-        .aperture(0.1)
+    // The integrator can be chosen with the first command line argument, and
+    // the rest are options
+    let integrator_name = std::env::args().nth(1);
+    let options: Vec<String> = std::env::args().skip(2).collect();
+    let has_option = |option: &str| options.iter().any(|other| other == option);
+
+    let Scene {
+        world,
+        look_from,
+        look_at,
+        vertical_fov,
+        camera: builder,
+    } = if has_option("caustics") {
+        caustics_scene()
+    } else {
+        random_scene()
+    };
+    let camera = builder
+        .clone()
+        .look_from(look_from)
+        .look_at(look_at)
+        .vertical_fov(vertical_fov)
         .build();
 
-    let image = create_image(&world, &camera, 600, 100);
+    // Progressive photon mapping needs to keep statistics of each pixel
+    // between iterations, so it renders the image by itself
+    if integrator_name.as_deref() == Some("sppm") {
+        let sppm = ProgressivePhotonMapping::new(100, 100_000, 0.1, 8);
+        print!("{}", sppm.create_image(&world, &camera, 600));
+        return;
+    }
+
+    // With the `turntable` option, the camera goes around the scene and each
+    // frame is saved in the `frames` directory
+    if has_option("turntable") {
+        let path = turntable(builder.focus_on_look_at(), look_from, look_at, vertical_fov);
+        let frames = FrameSequence::new(0.0..1.0, 120, "frames");
+        let name = integrator_name.as_deref();
+        let result = path.render_frames(
+            &world,
+            &frames,
+            |world, camera| integrator(name, world, camera),
+            600,
+            100,
+        );
+        if let Err(err) = result {
+            eprintln!("Couldn't save the frames: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let integrator = integrator(integrator_name.as_deref(), &world, &camera);
+    let image = create_image(&world, &camera, integrator.as_ref(), 600, 100);
     print!("{image}");
 }
 
+/// Return the integrator called `name`, or a path tracer if there's no name
+fn integrator<'a>(
+    name: Option<&str>,
+    world: &'a HittableList,
+    camera: &'a Camera,
+) -> Box<dyn Integrator + 'a> {
+    match name {
+        None | Some("path") => Box::new(PathTracer::new(50)),
+        Some("spectral") => Box::new(SpectralPathTracer::new(50)),
+        Some("bdpt") => Box::new(BidirectionalPathTracer::new(world, camera, 8)),
+        Some("photon-mapping") => Box::new(PhotonMapping::new(world, 1_000_000, 0.05, 8)),
+        Some("ambient-occlusion") => Box::new(AmbientOcclusion::new(16, 1.0)),
+        Some("normals") => Box::new(debug_integrators::Normals),
+        Some("depth") => Box::new(debug_integrators::Depth::new(camera, 20.0)),
+        Some("distance") => Box::new(debug_integrators::HitDistance::new(20.0)),
+        Some("front-face") => Box::new(debug_integrators::FrontFace),
+        Some("material-id") => Box::new(debug_integrators::MaterialId),
+        Some("uv") => Box::new(debug_integrators::Uv),
+        Some("traversal-cost") => Box::new(debug_integrators::TraversalCost::new(500)),
+        Some(other) => {
+            eprintln!("Unknown integrator '{other}'");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Return a path that goes once around the vertical axis through `look_at`
+/// between the times 0.0 and 1.0, starting at `look_from`
+fn turntable(
+    builder: CameraBuilder,
+    look_from: Vec3,
+    look_at: Vec3,
+    vertical_fov: Degrees,
+) -> CameraPath {
+    const KEYFRAMES: i32 = 8;
+    let offset = look_from - look_at;
+    let radius = offset.x.hypot(offset.z);
+    let start = offset.z.atan2(offset.x);
+
+    // Keyframes before the start and after the end make the path smooth
+    // where it loops
+    (-1..=KEYFRAMES + 1).fold(CameraPath::new(builder), |path, i| {
+        let time = i as f64 / KEYFRAMES as f64;
+        let angle = start + 2.0 * std::f64::consts::PI * time;
+        let look_from = look_at + vec3!(radius * angle.cos(), offset.y, radius * angle.sin());
+        path.with_keyframe(Keyframe::new(time, look_from, look_at, vertical_fov))
+    })
+}
+
+/// Creates the final scene of the book, with many random small spheres
+fn random_scene() -> Scene {
+    Scene {
+        world: random_world(),
+        look_from: vec3!(13.0, 2.0, 3.0),
+        look_at: Vec3::zero(),
+        vertical_fov: 20.0.into(),
+        camera: Camera::builder()
+            .aspect_ratio(3.0 / 2.0)
+            .up_vector(vec3!(0.0, 1.0, 0.0))
+            .focus_distance(10.0)
+            .aperture(0.1),
+    }
+}
+
+/// Creates a glass sphere on a floor lit by a small light, inside a room
+/// that hides the sky. The light focused by the sphere makes a caustic on
+/// the floor, which is hard to render for path tracers.
+fn caustics_scene() -> Scene {
+    let mut world = HittableList::new();
+    let room = Rc::new(Lambertian::new(color!(0.6, 0.6, 0.6)));
+    world.add(Sphere::new(Vec3::zero(), 20.0, room));
+    let floor = Rc::new(Lambertian::new(color!(0.7, 0.7, 0.7)));
+    world.add(Sphere::new(vec3!(0.0, -1000.0, 0.0), 1000.0, floor));
+    let glass = Rc::new(Dielectric::new(1.5));
+    world.add(Sphere::new(vec3!(0.0, 1.0, 0.0), 1.0, glass));
+    let light = Rc::new(DiffuseLight::new(color!(40.0, 40.0, 40.0)));
+    world.add(Sphere::new(vec3!(-3.0, 6.0, -2.0), 0.5, light));
+
+    Scene {
+        world,
+        look_from: vec3!(0.0, 4.0, 9.0),
+        look_at: vec3!(0.5, 0.5, 0.0),
+        vertical_fov: 30.0.into(),
+        camera: Camera::builder().aspect_ratio(3.0 / 2.0),
+    }
+}
+
 /// Creates a `HittableList` pre-populated with several items
 fn random_world() -> HittableList {
     let mut world = HittableList::new();
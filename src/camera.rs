@@ -1,22 +1,184 @@
+use std::rc::Rc;
+
 use crate::{
-    rtweekend::{Degrees, Radians},
+    angle::{Degrees, Radians},
+    aperture::{Aperture, ApertureShape},
+    color::Color,
+    exposure::Exposure,
+    hittable::Hittable,
+    lens_system::{FocusedLens, LensSystem},
+    rtweekend::PI,
     vec3,
     vector3::Vec3,
     Ray,
 };
 
+/// How a `Camera` maps points of the film to rays
+#[derive(Clone)]
+pub enum Projection {
+    /// Rays leave from a single point (or from the lens) and spread out
+    /// according to the vertical field of view, like in a real camera
+    Perspective,
+    /// Rays are all parallel to the view direction and leave from a view
+    /// plane of the given size, centered at the point the camera looks from.
+    /// Parallel lines stay parallel, as in technical drawings.
+    Orthographic { width: f64, height: f64 },
+    /// Rays go in every direction around the camera, with the longitude
+    /// along the horizontal axis of the film and the latitude along the
+    /// vertical one, like in environment maps. The view direction is at the
+    /// center of the film, and the aspect ratio is always 2:1.
+    Equirectangular,
+    /// Rays within a field of view (which can be larger than 180 degrees)
+    /// fill a circle that touches the edges of the film, mapped to it as by
+    /// a fisheye lens. The aspect ratio is always 1:1, and the corners of
+    /// the film, outside of the circle, are black.
+    Fisheye {
+        mapping: FisheyeMapping,
+        fov: Degrees,
+    },
+    /// Rays are traced from the film through a system of real lenses, which
+    /// sets the field of view. The focus distance is measured from the film,
+    /// and the aperture is the one of the lens system.
+    LensSystem(Rc<LensSystem>),
+}
+
+/// How the angle between a ray and the view direction relates to the distance
+/// from the center of the image in a fisheye projection
+#[derive(Clone, Copy)]
+pub enum FisheyeMapping {
+    /// The distance is proportional to the angle
+    Equidistant,
+    /// Every point of the image covers the same solid angle
+    Equisolid,
+}
+
+/// How the eyes of a stereo camera are aimed
+#[derive(Clone, Copy)]
+pub enum StereoMode {
+    /// Both eyes look in the same direction, and their images are shifted so
+    /// that they match at the convergence distance. This avoids the vertical
+    /// parallax of toe-in.
+    OffAxis,
+    /// Each eye is rotated to look at the point at the convergence distance
+    ToeIn,
+}
+
+/// How the images of both eyes are placed in the image of a stereo camera
+#[derive(Clone, Copy)]
+pub enum StereoLayout {
+    /// Left eye on the left half, right eye on the right half
+    SideBySide,
+    /// Left eye on the top half, right eye on the bottom half
+    OverUnder,
+}
+
+/// Which eye of a stereo camera
+#[derive(Clone, Copy)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+/// Settings of a stereo camera, which renders the scene from two points as
+/// seen by each eye
+#[derive(Clone, Copy)]
+pub struct Stereo {
+    interocular_distance: f64,
+    convergence_distance: f64,
+    mode: StereoMode,
+    layout: StereoLayout,
+}
+
+impl Stereo {
+    /// Create stereo settings with the distance between the eyes and the
+    /// distance at which objects appear at the depth of the screen. Eyes are
+    /// off-axis and their images are placed side by side.
+    pub fn new(interocular_distance: f64, convergence_distance: f64) -> Stereo {
+        Stereo {
+            interocular_distance,
+            convergence_distance,
+            mode: StereoMode::OffAxis,
+            layout: StereoLayout::SideBySide,
+        }
+    }
+    pub fn with_mode(mut self, mode: StereoMode) -> Stereo {
+        self.mode = mode;
+        self
+    }
+    pub fn with_layout(mut self, layout: StereoLayout) -> Stereo {
+        self.layout = layout;
+        self
+    }
+}
+
+/// Height of the film of a 35 mm camera, in millimeters, used to convert
+/// between focal lengths and fields of view
+const FILM_HEIGHT: f64 = 24.0;
+
+/// Number of rays averaged by `CameraBuilder::build_with` to find the ray
+/// that goes through the middle of the lens
+const AUTOFOCUS_SAMPLES: usize = 64;
+
+/// Distance at which a `CameraBuilder` focuses
+#[derive(Clone, Copy)]
+enum Focus {
+    Distance(f64),
+    LookAt,
+    /// Whatever is seen at a point of the film, in the world given when
+    /// building the camera
+    Hit {
+        s: f64,
+        t: f64,
+    },
+}
+
+/// Size of the aperture of a `CameraBuilder`
+#[derive(Clone, Copy)]
+enum ApertureSize {
+    Diameter(f64),
+    FNumber(f64),
+}
+
 /// A builder to create a Camera
+#[derive(Clone)]
 pub struct CameraBuilder {
+    projection: Projection,
+    stereo: Option<Stereo>,
     look_from: Vec3,
     look_at: Vec3,
     up_vector: Vec3,
     vertical_fov: Degrees,
     aspect_ratio: f64,
-    aperture: f64,
-    focus_distance: f64,
+    aperture: ApertureSize,
+    aperture_shape: ApertureShape,
+    cats_eye: f64,
+    focus: Focus,
+    lens_shift: (f64, f64),
+    tilt: Degrees,
+    swing: Degrees,
+    exposure: Exposure,
+    exposure_compensation: f64,
 }
 
 impl CameraBuilder {
+    /// Projection of the camera, which is `Projection::Perspective` by
+    /// default
+    pub fn projection(mut self, projection: Projection) -> CameraBuilder {
+        self.projection = projection;
+        self
+    }
+    /// Use an orthographic projection with a view plane of a given width and
+    /// height, whose ratio becomes the aspect ratio of the camera
+    pub fn orthographic(self, width: f64, height: f64) -> CameraBuilder {
+        self.projection(Projection::Orthographic { width, height })
+    }
+    /// Render the scene for both eyes, in a single image with the layout of
+    /// `stereo`. The aspect ratio is the one of each eye, so the whole image
+    /// is twice as wide (or half as tall) as it says.
+    pub fn stereo(mut self, stereo: Stereo) -> CameraBuilder {
+        self.stereo = Some(stereo);
+        self
+    }
     /// Point from which the camera will look from
     pub fn look_from(mut self, point: Vec3) -> CameraBuilder {
         self.look_from = point;
@@ -32,7 +194,8 @@ impl CameraBuilder {
         self.up_vector = up_vector;
         self
     }
-    /// Vertical field of view for the camera in degrees
+    /// Vertical field of view for the camera in degrees, used by the
+    /// perspective projection
     pub fn vertical_fov(mut self, field_of_view: Degrees) -> CameraBuilder {
         self.vertical_fov = field_of_view;
         self
@@ -42,62 +205,351 @@ impl CameraBuilder {
         self.aspect_ratio = aspect_ratio;
         self
     }
+    /// Vertical field of view of a 35 mm camera with a lens of the given
+    /// focal length in millimeters, like 24.0 for a wide angle or 85.0 for a
+    /// portrait lens
+    pub fn focal_length(self, focal_length: f64) -> CameraBuilder {
+        let fov = Radians::from(2.0 * (FILM_HEIGHT / 2.0 / focal_length).atan());
+        self.vertical_fov(fov.into())
+    }
     /// Aperture of the camera
     pub fn aperture(mut self, aperture: f64) -> CameraBuilder {
-        self.aperture = aperture;
+        self.aperture = ApertureSize::Diameter(aperture);
+        self
+    }
+    /// Aperture of the camera given as an f-number, like 1.4 or 8.0, which is
+    /// the focal length of the lens (found from the field of view as in
+    /// `focal_length`) divided by the diameter of the aperture. Scenes are
+    /// assumed to be in meters, so a 50 mm lens at f/2 has an aperture of
+    /// 0.025.
+    pub fn f_number(mut self, f_number: f64) -> CameraBuilder {
+        self.aperture = ApertureSize::FNumber(f_number);
+        self
+    }
+    /// Shape of the aperture, which is a circle by default. Its size is
+    /// still given by `aperture`, which is the diameter of the circle that
+    /// contains it.
+    pub fn aperture_shape(mut self, shape: ApertureShape) -> CameraBuilder {
+        self.aperture_shape = shape;
+        self
+    }
+    /// Clip the aperture seen from points away from the center of the film,
+    /// as the barrel of a real lens does, so that out-of-focus highlights
+    /// near the edges of the image take the shape of a cat's eye and get
+    /// darker. A strength of 0.0 (the default) disables it, and at 1.0 the
+    /// aperture seen from the corners of the film is half as wide.
+    pub fn cats_eye(mut self, strength: f64) -> CameraBuilder {
+        self.cats_eye = strength.max(0.0);
         self
     }
     /// Focus distance of the camera
     pub fn focus_distance(mut self, focus_distance: f64) -> CameraBuilder {
-        self.focus_distance = focus_distance;
+        self.focus = Focus::Distance(focus_distance);
+        self
+    }
+    /// Focus on the `look_at` point, wherever it ends up being
+    pub fn focus_on_look_at(mut self) -> CameraBuilder {
+        self.focus = Focus::LookAt;
+        self
+    }
+    /// Focus on whatever the ray through the center of the image hits in the
+    /// world given to `build_with`, with the settings the camera has when
+    /// it's built. Cameras built with `build`, or whose ray doesn't hit
+    /// anything, focus on the `look_at` point instead.
+    pub fn autofocus(self) -> CameraBuilder {
+        self.autofocus_at(0.5, 0.5)
+    }
+    /// Focus on whatever is seen at a pixel of an image with the given
+    /// size, counting pixels from the top left corner of the image, in the
+    /// same way as `autofocus`
+    pub fn autofocus_on_pixel(
+        self,
+        x: usize,
+        y: usize,
+        image_width: usize,
+        image_height: usize,
+    ) -> CameraBuilder {
+        let s = (x as f64 + 0.5) / image_width as f64;
+        let t = 1.0 - (y as f64 + 0.5) / image_height as f64;
+        self.autofocus_at(s, t)
+    }
+    /// Focus on whatever is seen at the point `(s, t)` of the film, using
+    /// the same coordinates as `Camera::get_ray`
+    fn autofocus_at(mut self, s: f64, t: f64) -> CameraBuilder {
+        self.focus = Focus::Hit { s, t };
+        self
+    }
+    /// Shift the lens parallel to the film, by fractions of the width and
+    /// height of the image. This moves the image without turning the
+    /// camera, so that vertical lines stay vertical when looking up at a
+    /// building with a vertical shift.
+    pub fn lens_shift(mut self, horizontal: f64, vertical: f64) -> CameraBuilder {
+        self.lens_shift = (horizontal, vertical);
+        self
+    }
+    /// Tilt the plane in focus around the horizontal axis of the camera, so
+    /// that its top goes away from the camera for positive angles. With a
+    /// large aperture, this makes scenes look like miniatures.
+    pub fn tilt(mut self, angle: Degrees) -> CameraBuilder {
+        self.tilt = angle;
+        self
+    }
+    /// Swing the plane in focus around the vertical axis of the camera, so
+    /// that its right side goes away from the camera for positive angles
+    pub fn swing(mut self, angle: Degrees) -> CameraBuilder {
+        self.swing = angle;
+        self
+    }
+    /// How the light that reaches the film is turned into pixel values
+    pub fn exposure(mut self, exposure: Exposure) -> CameraBuilder {
+        self.exposure = exposure;
         self
     }
+    /// Make the image brighter by the given number of stops (exposure
+    /// values), or darker with negative ones. Each stop doubles the
+    /// brightness.
+    pub fn exposure_compensation(mut self, stops: f64) -> CameraBuilder {
+        self.exposure_compensation = stops;
+        self
+    }
+    /// Create a Camera like `build` does, but focusing on what's seen in
+    /// `world` if `autofocus` or `autofocus_on_pixel` were used
+    pub fn build_with(&self, world: &dyn Hittable) -> Camera {
+        match self.focus {
+            Focus::Hit { s, t } => match self.distance_seen_at(world, s, t) {
+                Some(distance) => self.clone().focus_distance(distance).build(),
+                None => self.build(),
+            },
+            _ => self.build(),
+        }
+    }
+
+    /// Return the distance along the view direction to whatever is seen at
+    /// the point `(s, t)` of the film in `world`, or `None` if nothing is
+    fn distance_seen_at(&self, world: &dyn Hittable, s: f64, t: f64) -> Option<f64> {
+        // Average the rays that go through the whole lens, which gives the
+        // ray that goes through its middle. Without an aperture they are all
+        // the same.
+        let camera = CameraBuilder {
+            aperture: ApertureSize::Diameter(0.0),
+            ..self.clone()
+        }
+        .build_eye();
+        let rays: Vec<Ray> = (0..AUTOFOCUS_SAMPLES)
+            .filter_map(|_| camera.get_ray(s, t))
+            .collect();
+        if rays.is_empty() {
+            return None;
+        }
+        let count = rays.len() as f64;
+        let origin = rays.iter().fold(Vec3::zero(), |sum, ray| sum + ray.origin) / count;
+        let direction = rays
+            .iter()
+            .fold(Vec3::zero(), |sum, ray| sum + ray.direction.unit_vec());
+
+        let hit = world.hit(&Ray::new(origin, direction), &(0.001..f64::INFINITY))?;
+        Some((hit.point - self.look_from).dot(camera.view_direction()))
+    }
+
     /// Create a Camera with the supplied values. If some values were not
     /// supplied by the user, the default ones will be used instead. Calling
     /// this function again will create the same Camera.
     pub fn build(&self) -> Camera {
-        let theta: Radians = self.vertical_fov.into();
-        let h = (theta.as_f64() / 2.0).tan();
-        let viewport_height = 2.0 * h;
-        let viewport_width = self.aspect_ratio * viewport_height;
+        let mut camera = self.build_eye();
+        if let Some(stereo) = self.stereo {
+            let eyes = StereoEyes {
+                left: self.stereo_eye(&stereo, &camera, Eye::Left),
+                right: self.stereo_eye(&stereo, &camera, Eye::Right),
+                layout: stereo.layout,
+            };
+            camera.aspect_ratio = match stereo.layout {
+                StereoLayout::SideBySide => 2.0 * camera.aspect_ratio,
+                StereoLayout::OverUnder => camera.aspect_ratio / 2.0,
+            };
+            camera.stereo = Some(Box::new(eyes));
+        }
+        camera
+    }
+
+    /// Create the camera of one eye of a stereo camera whose middle camera
+    /// is `center`
+    fn stereo_eye(&self, stereo: &Stereo, center: &Camera, eye: Eye) -> Camera {
+        let side = match eye {
+            Eye::Left => -0.5,
+            Eye::Right => 0.5,
+        };
+        let offset = side * stereo.interocular_distance * center.u;
+        let convergence_point = self.look_from - stereo.convergence_distance * center.w;
+        let look_at = match stereo.mode {
+            StereoMode::OffAxis => self.look_at + offset,
+            StereoMode::ToeIn => convergence_point,
+        };
+        let builder = CameraBuilder {
+            stereo: None,
+            look_from: self.look_from + offset,
+            look_at,
+            ..self.clone()
+        };
+        let mut camera = builder.build_eye();
+
+        // Shift the film in the opposite direction of the eye, so that the
+        // film of both eyes covers the same area at the convergence distance
+        if let (StereoMode::OffAxis, Projection::Perspective) = (stereo.mode, &self.projection) {
+            camera.lower_left_corner -=
+                (self.resolved_focus_distance() / stereo.convergence_distance) * offset;
+        }
+        camera
+    }
 
+    /// Return the distance at which the camera focuses
+    fn resolved_focus_distance(&self) -> f64 {
+        match self.focus {
+            Focus::Distance(distance) => distance,
+            Focus::LookAt | Focus::Hit { .. } => (self.look_at - self.look_from).length(),
+        }
+    }
+
+    /// Return the f-number of the aperture, which is infinite without one
+    fn resolved_f_number(&self) -> f64 {
+        if let Projection::LensSystem(lens_system) = &self.projection {
+            return lens_system.f_number().unwrap_or(f64::INFINITY);
+        }
+        match self.aperture {
+            ApertureSize::FNumber(f_number) => f_number,
+            ApertureSize::Diameter(diameter) => self.focal_length_in_meters() / diameter,
+        }
+    }
+
+    /// Return the focal length of a 35 mm camera with the vertical field of
+    /// view of this one, in meters
+    fn focal_length_in_meters(&self) -> f64 {
+        let focal_length = FILM_HEIGHT / 2.0 / (self.vertical_fov / 2.0).tan();
+        // Focal lengths are in millimeters and scenes in meters
+        focal_length / 1000.0
+    }
+
+    /// Return the diameter of the aperture
+    fn aperture_diameter(&self) -> f64 {
+        match self.aperture {
+            ApertureSize::Diameter(diameter) => diameter,
+            ApertureSize::FNumber(f_number) => self.focal_length_in_meters() / f_number,
+        }
+    }
+
+    /// Create a Camera for a single eye
+    fn build_eye(&self) -> Camera {
+        let focus_distance = self.resolved_focus_distance();
         // `w` is the opposite direction of where the camera looks at
         let w = (self.look_from - self.look_at).unit_vec();
         let u = self.up_vector.cross(w).unit_vec();
         let v = w.cross(u);
-
         let origin = self.look_from;
-        let horizontal = self.focus_distance * viewport_width * u;
-        let vertical = self.focus_distance * viewport_height * v;
-        let lower_left_corner =
-            origin - horizontal / 2.0 - vertical / 2.0 - self.focus_distance * w;
 
-        let lens_radius = self.aperture / 2.0;
+        let (horizontal, vertical, lower_left_corner, aspect_ratio) = match self.projection {
+            Projection::Perspective
+            | Projection::Equirectangular
+            | Projection::Fisheye { .. }
+            | Projection::LensSystem(_) => {
+                let h = (self.vertical_fov / 2.0).tan();
+                let viewport_height = 2.0 * h;
+                let viewport_width = self.aspect_ratio * viewport_height;
+
+                let horizontal = focus_distance * viewport_width * u;
+                let vertical = focus_distance * viewport_height * v;
+                let lower_left_corner =
+                    origin - horizontal / 2.0 - vertical / 2.0 - focus_distance * w;
+                let aspect_ratio = match self.projection {
+                    Projection::Equirectangular => 2.0,
+                    Projection::Fisheye { .. } => 1.0,
+                    _ => self.aspect_ratio,
+                };
+                (horizontal, vertical, lower_left_corner, aspect_ratio)
+            }
+            Projection::Orthographic { width, height } => {
+                // The view plane goes through `origin`
+                let horizontal = width * u;
+                let vertical = height * v;
+                let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0;
+                (horizontal, vertical, lower_left_corner, width / height)
+            }
+        };
+        let (shift_x, shift_y) = self.lens_shift;
+        let lower_left_corner = lower_left_corner + shift_x * horizontal + shift_y * vertical;
+
+        // The plane in focus contains the directions `v - tan(tilt) w` and
+        // `u - tan(swing) w`
+        let focal_plane_normal = (w + self.tilt.tan() * v + self.swing.tan() * u).unit_vec();
+
+        // Area of the film when placed at distance 1.0 from the lens
+        let film_area = horizontal.length() * vertical.length() / (focus_distance * focus_distance);
+
+        let lens_radius = self.aperture_diameter() / 2.0;
+
+        let lens = match &self.projection {
+            Projection::LensSystem(lens_system) => {
+                Some(lens_system.focus(focus_distance, aspect_ratio))
+            }
+            _ => None,
+        };
 
         Camera {
+            projection: self.projection.clone(),
+            stereo: None,
+            lens,
             origin,
             lower_left_corner,
             horizontal,
             vertical,
             u,
             v,
-            _w: w,
+            w,
             lens_radius,
-            aspect_ratio: self.aspect_ratio,
+            aperture: Aperture::new(&self.aperture_shape),
+            cats_eye: self.cats_eye,
+            focus_distance,
+            focal_plane_normal,
+            film_area,
+            aspect_ratio,
+            f_number: self.resolved_f_number(),
+            exposure: self.exposure,
+            exposure_compensation: self.exposure_compensation,
         }
     }
 }
 
+/// Cameras of both eyes of a stereo camera
+struct StereoEyes {
+    left: Camera,
+    right: Camera,
+    layout: StereoLayout,
+}
+
 pub struct Camera {
+    projection: Projection,
+    stereo: Option<Box<StereoEyes>>,
+    /// Lens system of the `Projection::LensSystem` projection, focused
+    lens: Option<FocusedLens>,
     origin: Vec3,
     lower_left_corner: Vec3,
     horizontal: Vec3,
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
-    _w: Vec3,
+    w: Vec3,
     lens_radius: f64,
+    aperture: Aperture,
+    cats_eye: f64,
+    focus_distance: f64,
+    /// Normal of the plane in focus, which goes through the point at
+    /// `focus_distance` in the view direction
+    focal_plane_normal: Vec3,
+    /// Area of the film when placed at distance 1.0 from the lens
+    film_area: f64,
     aspect_ratio: f64,
+    /// F-number of the aperture, used by `Exposure::Manual`
+    f_number: f64,
+    exposure: Exposure,
+    exposure_compensation: f64,
 }
 
 impl Camera {
@@ -105,13 +557,22 @@ impl Camera {
     pub fn builder() -> CameraBuilder {
         // fill with default values
         CameraBuilder {
+            projection: Projection::Perspective,
+            stereo: None,
             look_from: Vec3::zero(),
             look_at: vec3!(0.0, -1.0, 0.0),
             up_vector: vec3!(0.0, 1.0, 0.0),
             vertical_fov: 90.0.into(),
             aspect_ratio: 16.0 / 9.0,
-            aperture: 0.0,
-            focus_distance: 1.0,
+            aperture: ApertureSize::Diameter(0.0),
+            aperture_shape: ApertureShape::Circle,
+            cats_eye: 0.0,
+            focus: Focus::Distance(1.0),
+            lens_shift: (0.0, 0.0),
+            tilt: 0.0.into(),
+            swing: 0.0.into(),
+            exposure: Exposure::Radiance,
+            exposure_compensation: 0.0,
         }
     }
     /// Create a Camera with default values
@@ -123,22 +584,303 @@ impl Camera {
         self.aspect_ratio
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+    /// Turn the light that reached each pixel of the film into its value,
+    /// with the exposure of the camera
+    pub(crate) fn expose(&self, pixels: &mut [Color]) {
+        let scale = self
+            .exposure
+            .scale(pixels, self.f_number, self.exposure_compensation);
+        for pixel in pixels {
+            *pixel *= scale;
+        }
+    }
+
+    /// Split the pixels of an image rendered by a stereo camera, stored row
+    /// by row starting with the bottom one, into the images of the left and
+    /// right eyes, and return them with their width. Return `None` if this
+    /// isn't a stereo camera.
+    pub(crate) fn split_eyes(
+        &self,
+        pixels: &[Color],
+        image_width: usize,
+    ) -> Option<(Vec<Color>, Vec<Color>, usize)> {
+        let eyes = self.stereo.as_ref()?;
+        let image_height = pixels.len() / image_width;
+        let rows = pixels.chunks(image_width);
+
+        // The middle column or row of an image with an odd size is covered
+        // by both eyes, so it's left out of both
+        Some(match eyes.layout {
+            StereoLayout::SideBySide => {
+                let eye_width = image_width / 2;
+                let left = rows.clone().flat_map(|row| &row[..eye_width]).copied();
+                let right = rows.flat_map(|row| &row[image_width - eye_width..]);
+                (left.collect(), right.copied().collect(), eye_width)
+            }
+            StereoLayout::OverUnder => {
+                // The left eye is on the top half, and the first row is the
+                // bottom one
+                let eye_height = image_height / 2;
+                let right = pixels[..eye_height * image_width].to_vec();
+                let left = pixels[(image_height - eye_height) * image_width..].to_vec();
+                (left, right, image_width)
+            }
+        })
+    }
+
+    /// Return whether this camera renders the images of two eyes
+    pub fn is_stereo(&self) -> bool {
+        self.stereo.is_some()
+    }
+
+    /// Return the camera of one eye of a stereo camera, which renders only
+    /// the image of that eye, or this camera if it isn't a stereo camera.
+    /// To get the images of both eyes from a single render, use
+    /// `create_stereo_images` instead.
+    pub fn eye(&self, eye: Eye) -> &Camera {
+        match (&self.stereo, eye) {
+            (Some(eyes), Eye::Left) => &eyes.left,
+            (Some(eyes), Eye::Right) => &eyes.right,
+            (None, _) => self,
+        }
+    }
+
+    /// Unit vector pointing to where the camera looks at
+    pub fn view_direction(&self) -> Vec3 {
+        -self.w
+    }
+
+    /// Return a ray that goes through the point of the film with coordinates
+    /// `(s, t)`, both in the range [0.0, 1.0] and starting at the bottom left
+    /// corner. Return `None` if the projection doesn't cover that point.
+    pub fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        self.get_weighted_ray(s, t).map(|(ray, _)| ray)
+    }
+
+    /// Return a ray like `get_ray` does, and the weight by which the light
+    /// it carries has to be multiplied. The weight is 1.0 except for lens
+    /// systems, where less light reaches the edges of the film.
+    pub fn get_weighted_ray(&self, s: f64, t: f64) -> Option<(Ray, f64)> {
+        if let Some(eyes) = &self.stereo {
+            return match eyes.layout {
+                StereoLayout::SideBySide if s < 0.5 => eyes.left.get_weighted_ray(2.0 * s, t),
+                StereoLayout::SideBySide => eyes.right.get_weighted_ray(2.0 * s - 1.0, t),
+                StereoLayout::OverUnder if t >= 0.5 => eyes.left.get_weighted_ray(s, 2.0 * t - 1.0),
+                StereoLayout::OverUnder => eyes.right.get_weighted_ray(s, 2.0 * t),
+            };
+        }
+        let ray = match self.projection {
+            Projection::Perspective => self.perspective_ray(s, t),
+            Projection::Orthographic { .. } => self.orthographic_ray(s, t),
+            Projection::Equirectangular => Some(self.equirectangular_ray(s, t)),
+            Projection::Fisheye { mapping, fov } => self.fisheye_ray(s, t, mapping, fov),
+            Projection::LensSystem(_) => return self.lens_system_ray(s, t),
+        };
+        ray.map(|ray| (ray, 1.0))
+    }
+
+    /// Return a ray traced through the lens system, and its weight
+    fn lens_system_ray(&self, s: f64, t: f64) -> Option<(Ray, f64)> {
+        let (ray, weight) = self.lens.as_ref()?.sample_ray(s, t)?;
+        // The lens looks towards positive Z, and the film is at the camera's
+        // position
+        let to_world = |v: Vec3| v.x * self.u + v.y * self.v - v.z * self.w;
+        Some((
+            Ray::new(self.origin + to_world(ray.origin), to_world(ray.direction)),
+            weight,
+        ))
+    }
+
+    fn perspective_ray(&self, s: f64, t: f64) -> Option<Ray> {
         if self.lens_radius == 0.0 {
-            return Ray::new(
+            return Some(Ray::new(
                 self.origin,
                 self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin,
-            );
+            ));
+        }
+
+        let film_point = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+        let point_in_focus = self.point_in_focus(&Ray::new(self.origin, film_point - self.origin));
+        let origin_with_offset = self.origin + self.lens_offset(s, t)?;
+
+        Some(match point_in_focus {
+            Some(point) => Ray::new(origin_with_offset, point - origin_with_offset),
+            // Parts of a tilted plane in focus can be behind the camera, so
+            // they are at an infinite distance in front of it
+            None => Ray::new(origin_with_offset, film_point - self.origin),
+        })
+    }
+
+    /// Return the point where a ray that leaves from the center of the lens
+    /// hits the plane in focus, or `None` if it doesn't hit it
+    fn point_in_focus(&self, ray: &Ray) -> Option<Vec3> {
+        let center = self.origin - self.focus_distance * self.w;
+        let cos_theta = ray.direction.dot(self.focal_plane_normal);
+        let dist = (center - ray.origin).dot(self.focal_plane_normal) / cos_theta;
+        (dist > 0.0 && dist.is_finite()).then(|| ray.at(dist))
+    }
+
+    /// Return a ray of the orthographic projection, which leaves from the
+    /// view plane, or from the lens around it, and focuses at
+    /// `focus_distance` in front of it
+    fn orthographic_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let film_point = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+        if self.lens_radius == 0.0 {
+            return Some(Ray::new(film_point, -self.w));
+        }
+
+        let origin = film_point + self.lens_offset(s, t)?;
+        Some(match self.point_in_focus(&Ray::new(film_point, -self.w)) {
+            Some(point_in_focus) => Ray::new(origin, point_in_focus - origin),
+            None => Ray::new(origin, -self.w),
+        })
+    }
+
+    /// Return the offset from the center of the lens of a random point of
+    /// the aperture, for a ray that goes through the film coordinates
+    /// `(s, t)`, or `None` if the point is clipped by cat's eye vignetting
+    fn lens_offset(&self, s: f64, t: f64) -> Option<Vec3> {
+        let point = self.aperture.sample();
+        if self.is_vignetted(point, s, t) {
+            return None;
+        }
+        Some(self.lens_radius * (self.u * point.x + self.v * point.y))
+    }
+
+    /// Return true if a point of the aperture, given in the square
+    /// [-1.0, 1.0]², isn't seen from the film coordinates `(s, t)` because
+    /// of cat's eye vignetting
+    fn is_vignetted(&self, point: Vec3, s: f64, t: f64) -> bool {
+        if self.cats_eye == 0.0 {
+            return false;
+        }
+        // Position on the film, where the corners are at distance 1.0 from
+        // the center. The aperture is clipped by a circle that moves away
+        // from it the further the position is from the center.
+        let diagonal = (self.aspect_ratio * self.aspect_ratio + 1.0).sqrt();
+        let film = vec3!(
+            (2.0 * s - 1.0) * self.aspect_ratio / diagonal,
+            (2.0 * t - 1.0) / diagonal,
+            0.0
+        );
+        (point - self.cats_eye * film).length_squared() > 1.0
+    }
+
+    /// Return a ray of the equirectangular projection. Panoramic projections
+    /// have no lens, so every ray leaves from the camera's position.
+    fn equirectangular_ray(&self, s: f64, t: f64) -> Ray {
+        let longitude = 2.0 * PI * (s - 0.5);
+        let latitude = PI * (t - 0.5);
+        let horizontal = longitude.sin() * self.u - longitude.cos() * self.w;
+        let direction = latitude.cos() * horizontal + latitude.sin() * self.v;
+        Ray::new(self.origin, direction)
+    }
+
+    /// Return a ray of a fisheye projection, or `None` outside of its circle
+    fn fisheye_ray(&self, s: f64, t: f64, mapping: FisheyeMapping, fov: Degrees) -> Option<Ray> {
+        let (x, y) = (2.0 * s - 1.0, 2.0 * t - 1.0);
+        let radius = (x * x + y * y).sqrt();
+        if radius > 1.0 {
+            return None;
+        }
+
+        // Angle between the ray and the view direction
+        let max_theta = Radians::from(fov).as_f64() / 2.0;
+        let theta = match mapping {
+            FisheyeMapping::Equidistant => radius * max_theta,
+            FisheyeMapping::Equisolid => 2.0 * (radius * (max_theta / 2.0).sin()).asin(),
+        };
+
+        let sideways = if radius > 0.0 {
+            (x * self.u + y * self.v) / radius
+        } else {
+            Vec3::zero()
+        };
+        let direction = theta.sin() * sideways - theta.cos() * self.w;
+        Some(Ray::new(self.origin, direction))
+    }
+
+    /// Return true if rays from every point of the lens to every point of the
+    /// scene can be mapped back to the film, which is only possible with a
+    /// perspective projection of a single eye, whose plane in focus isn't
+    /// tilted unless it's a pinhole camera. Integrators that connect points
+    /// of the scene to the camera (like `BidirectionalPathTracer`) only do
+    /// so when this is true.
+    pub(crate) fn is_connectible(&self) -> bool {
+        let tilted = self.focal_plane_normal.dot(self.w) < 1.0 - 1e-12;
+        self.stereo.is_none()
+            && matches!(self.projection, Projection::Perspective)
+            && !(tilted && self.lens_radius > 0.0)
+    }
+
+    /// Return a random point of the lens, chosen as `get_ray` chooses them
+    pub(crate) fn sample_lens(&self) -> Vec3 {
+        let point = self.lens_radius * self.aperture.sample();
+        self.origin + self.u * point.x + self.v * point.y
+    }
+
+    /// Return the film coordinates `(s, t)` of a ray that leaves from the lens,
+    /// as they would have been passed to `get_ray` to generate it, or `None`
+    /// if the ray doesn't go through the film.
+    pub(crate) fn film_coordinates(&self, ray: &Ray) -> Option<(f64, f64)> {
+        if !self.is_connectible() {
+            return None;
         }
+        let cos_theta = ray.direction.dot(self.view_direction());
+        if cos_theta <= 0.0 {
+            return None;
+        }
+        let point_in_focus = ray.at(self.focus_distance / cos_theta);
+        let offset = point_in_focus - self.lower_left_corner;
+        let s = offset.dot(self.horizontal) / self.horizontal.length_squared();
+        let t = offset.dot(self.vertical) / self.vertical.length_squared();
+        if (0.0..1.0).contains(&s) && (0.0..1.0).contains(&t) {
+            Some((s, t))
+        } else {
+            None
+        }
+    }
 
-        let rd = self.lens_radius * Vec3::random_in_unit_disc();
-        let offset = self.u * rd.x + self.v * rd.y;
-        let origin_with_offset = self.origin + offset;
+    /// Return the importance emitted by the camera along a ray that leaves
+    /// from the lens, or 0.0 if the ray doesn't go through the film. The
+    /// importance is normalized so that it integrates to 1.0 over the film.
+    pub(crate) fn importance(&self, ray: &Ray) -> f64 {
+        let (s, t) = match self.film_coordinates(ray) {
+            Some(coordinates) => coordinates,
+            None => return 0.0,
+        };
+        if self.lens_radius > 0.0 && self.is_vignetted(self.aperture_point(ray.origin), s, t) {
+            return 0.0;
+        }
+        let cos_theta = ray.direction.dot(self.view_direction());
+        self.lens_pdf(ray.origin) / (self.film_area * cos_theta.powi(4))
+    }
 
-        Ray::new(
-            origin_with_offset,
-            self.lower_left_corner + s * self.horizontal + t * self.vertical - origin_with_offset,
-        )
+    /// Return the probability density (per unit solid angle) of the direction
+    /// of a ray returned by `get_ray` for random film coordinates
+    pub(crate) fn direction_pdf(&self, ray: &Ray) -> f64 {
+        if self.film_coordinates(ray).is_none() {
+            return 0.0;
+        }
+        let cos_theta = ray.direction.dot(self.view_direction());
+        1.0 / (self.film_area * cos_theta.powi(3))
+    }
+
+    /// Return the probability density (per unit area) with which the origin
+    /// of a ray returned by `get_ray` is `point`, or 1.0 for a pinhole camera
+    pub(crate) fn lens_pdf(&self, point: Vec3) -> f64 {
+        if self.lens_radius == 0.0 {
+            return 1.0;
+        }
+        let radius_squared = self.lens_radius * self.lens_radius;
+        self.aperture.pdf(self.aperture_point(point)) / radius_squared
+    }
+
+    /// Return a point of the lens in the coordinates of the aperture, where
+    /// the lens is the square [-1.0, 1.0]²
+    fn aperture_point(&self, point: Vec3) -> Vec3 {
+        let offset = (point - self.origin) / self.lens_radius;
+        vec3!(offset.dot(self.u), offset.dot(self.v), 0.0)
     }
 }
 
@@ -147,3 +889,100 @@ impl Default for Camera {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color, material::Lambertian, sphere::Sphere};
+
+    /// Return the pixels of an image whose red channel is the column and
+    /// green channel the row of each pixel
+    fn coordinates(width: usize, height: usize) -> Vec<Color> {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| color!(x as f64, y as f64, 0.0)))
+            .collect()
+    }
+
+    #[test]
+    fn splits_side_by_side_eyes() {
+        let camera = Camera::builder().stereo(Stereo::new(0.065, 2.0)).build();
+        let (left, right, eye_width) = camera.split_eyes(&coordinates(5, 2), 5).unwrap();
+
+        assert_eq!(eye_width, 2);
+        assert_eq!(
+            left.iter().map(|c| c.red).collect::<Vec<_>>(),
+            [0.0, 1.0, 0.0, 1.0]
+        );
+        assert_eq!(
+            right.iter().map(|c| c.red).collect::<Vec<_>>(),
+            [3.0, 4.0, 3.0, 4.0]
+        );
+    }
+
+    #[test]
+    fn splits_over_under_eyes() {
+        let stereo = Stereo::new(0.065, 2.0).with_layout(StereoLayout::OverUnder);
+        let camera = Camera::builder().stereo(stereo).build();
+        let (left, right, eye_width) = camera.split_eyes(&coordinates(2, 5), 2).unwrap();
+
+        // The first row is the bottom one, which belongs to the right eye
+        assert_eq!(eye_width, 2);
+        assert_eq!(
+            left.iter().map(|c| c.green).collect::<Vec<_>>(),
+            [3.0, 3.0, 4.0, 4.0]
+        );
+        assert_eq!(
+            right.iter().map(|c| c.green).collect::<Vec<_>>(),
+            [0.0, 0.0, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn autofocus_uses_the_position_at_build_time() {
+        let material = Rc::new(Lambertian::new(color!(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(vec3!(0.0, 0.0, -5.0), 1.0, material);
+
+        // The camera moves after asking for autofocus
+        let camera = Camera::builder()
+            .autofocus()
+            .look_from(vec3!(0.0, 0.0, 2.0))
+            .look_at(vec3!(0.0, 0.0, -1.0))
+            .build_with(&sphere);
+        assert!((camera.focus_distance - 6.0).abs() < 1e-9);
+
+        // Without a world, or when nothing is hit, it focuses on `look_at`
+        let camera = Camera::builder()
+            .autofocus_on_pixel(0, 0, 10, 10)
+            .look_from(vec3!(0.0, 0.0, 2.0))
+            .look_at(vec3!(0.0, 0.0, -1.0))
+            .build_with(&sphere);
+        assert!((camera.focus_distance - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn manual_exposure_uses_the_aperture_of_the_camera() {
+        let exposure = Exposure::Manual {
+            iso: 100.0,
+            shutter_time: 1.0 / 125.0,
+        };
+        let exposed = |builder: CameraBuilder| {
+            let mut pixels = [color!(1.0, 1.0, 1.0)];
+            builder.exposure(exposure).build().expose(&mut pixels);
+            pixels[0].red
+        };
+
+        // A 50 mm lens at f/16 has an aperture of 3.125 mm
+        let f_16 = exposed(Camera::builder().focal_length(50.0).f_number(16.0));
+        let diameter = exposed(Camera::builder().focal_length(50.0).aperture(0.003125));
+        assert!((f_16 - diameter).abs() < 1e-9 * f_16);
+
+        // Closing the aperture by two stops lets in a quarter of the light
+        let f_32 = exposed(Camera::builder().focal_length(50.0).f_number(32.0));
+        assert!((f_32 - f_16 / 4.0).abs() < 1e-9 * f_16);
+    }
+
+    #[test]
+    fn mono_cameras_have_no_eyes() {
+        assert!(Camera::new().split_eyes(&coordinates(2, 2), 2).is_none());
+    }
+}
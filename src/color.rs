@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign};
 
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 pub struct Color {
     pub red: f64,
     pub green: f64,
@@ -20,6 +20,18 @@ macro_rules! color {
     };
 }
 
+impl Color {
+    /// Return true if every component of this color is zero (or negative)
+    pub fn is_black(&self) -> bool {
+        self.red <= 0.0 && self.green <= 0.0 && self.blue <= 0.0
+    }
+
+    /// Return the luminance of this color, taken as a linear RGB color
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
+}
+
 impl Add for Color {
     type Output = Self;
 
@@ -0,0 +1,59 @@
+use std::rc::Rc;
+
+use crate::{
+    color::Color, hittable::HitRecord, material::Material, ray::Ray, subsurface::Medium,
+    texture::Texture, vector3::Vec3,
+};
+
+/// Material that cuts holes in another one with an opacity texture, like the
+/// alpha channel of a leaf, fence or decal image. The opacity at each point is
+/// the luminance of the texture, so black parts are fully transparent and
+/// white ones fully opaque.
+///
+/// Rays that go through the holes keep going as if the surface wasn't there,
+/// both for camera rays and for shadows.
+pub struct Cutout {
+    opacity: Rc<dyn Texture>,
+    material: Rc<dyn Material>,
+}
+
+impl Cutout {
+    pub fn new(opacity: Rc<dyn Texture>, material: Rc<dyn Material>) -> Cutout {
+        Cutout { opacity, material }
+    }
+}
+
+impl Material for Cutout {
+    fn scatter(&self, ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
+        self.material.scatter(ray_in, hit)
+    }
+
+    fn emitted(&self, hit: &HitRecord) -> Color {
+        self.material.emitted(hit)
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.material.is_dispersive()
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.material.medium()
+    }
+
+    fn opacity(&self, hit: &HitRecord) -> f64 {
+        let opacity = self.opacity.value(hit.u, hit.v, hit.point).luminance();
+        opacity.clamp(0.0, 1.0) * self.material.opacity(hit)
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, hit: &HitRecord) -> Option<Color> {
+        self.material.eval(wo, wi, hit)
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, hit: &HitRecord) -> f64 {
+        self.material.pdf(wo, wi, hit)
+    }
+}
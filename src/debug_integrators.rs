@@ -0,0 +1,152 @@
+use std::rc::Rc;
+
+use crate::{
+    camera::Camera, color, color::Color, hittable::Hittable, integrator::Integrator, ray::Ray,
+    rtweekend::clamp, vector3::Vec3,
+};
+
+/// Range of distances in which debug integrators look for hits
+const HIT_RANGE: std::ops::Range<f64> = 0.001..f64::INFINITY;
+
+/// Color used by debug integrators when a ray doesn't hit anything
+fn miss_color() -> Color {
+    color!(0.0, 0.0, 0.0)
+}
+
+/// Return a gray color whose brightness is `value` divided by `max_value`
+fn grayscale(value: f64, max_value: f64) -> Color {
+    let brightness = clamp(value / max_value, 0.0..=1.0);
+    color!(brightness, brightness, brightness)
+}
+
+/// Show the normal vector of the surface that was hit, mapping each of its
+/// components from [-1.0, 1.0] to [0.0, 1.0]
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color {
+        match world.hit(ray, &HIT_RANGE) {
+            Some(hit) => {
+                let Vec3 { x, y, z } = hit.normal.unit_vec();
+                0.5 * color!(x + 1.0, y + 1.0, z + 1.0)
+            }
+            None => miss_color(),
+        }
+    }
+}
+
+/// Show the depth of the hit point, which is its distance to the camera
+/// measured along the camera's view direction. Points at `max_depth` or
+/// further away are white.
+pub struct Depth {
+    view_direction: Vec3,
+    max_depth: f64,
+}
+
+impl Depth {
+    pub fn new(camera: &Camera, max_depth: f64) -> Depth {
+        Depth {
+            view_direction: camera.view_direction(),
+            max_depth,
+        }
+    }
+}
+
+impl Integrator for Depth {
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color {
+        match world.hit(ray, &HIT_RANGE) {
+            Some(hit) => {
+                let depth = hit.dist * ray.direction.dot(self.view_direction);
+                grayscale(depth, self.max_depth)
+            }
+            None => miss_color(),
+        }
+    }
+}
+
+/// Show the distance from the ray's origin to the hit point. Points at
+/// `max_distance` or further away are white.
+pub struct HitDistance {
+    max_distance: f64,
+}
+
+impl HitDistance {
+    pub fn new(max_distance: f64) -> HitDistance {
+        HitDistance { max_distance }
+    }
+}
+
+impl Integrator for HitDistance {
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color {
+        match world.hit(ray, &HIT_RANGE) {
+            Some(hit) => grayscale(hit.dist, self.max_distance),
+            None => miss_color(),
+        }
+    }
+}
+
+/// Show in green the hits on the front face of a surface, and in red the
+/// hits on its back face
+pub struct FrontFace;
+
+impl Integrator for FrontFace {
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color {
+        match world.hit(ray, &HIT_RANGE) {
+            Some(hit) if hit.front_face => color!(0.0, 1.0, 0.0),
+            Some(_) => color!(1.0, 0.0, 0.0),
+            None => miss_color(),
+        }
+    }
+}
+
+/// Give every material in the scene a different color. Surfaces that share
+/// the same `Rc<dyn Material>` are shown with the same color.
+pub struct MaterialId;
+
+impl Integrator for MaterialId {
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color {
+        match world.hit(ray, &HIT_RANGE) {
+            Some(hit) => {
+                let id = Rc::as_ptr(&hit.material) as *const u8 as usize as u64;
+                // Scramble the bits of the address so that materials which
+                // are close in memory get very different colors
+                let hash = id.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                let channel = |shift: u32| ((hash >> shift) & 0xFF) as f64 / 255.0;
+                color!(channel(40), channel(48), channel(56))
+            }
+            None => miss_color(),
+        }
+    }
+}
+
+/// Show the surface coordinates of the hit point, with `u` in the red
+/// channel and `v` in the green channel
+pub struct Uv;
+
+impl Integrator for Uv {
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color {
+        match world.hit(ray, &HIT_RANGE) {
+            Some(hit) => color!(hit.u, hit.v, 0.0),
+            None => miss_color(),
+        }
+    }
+}
+
+/// Show how many intersection tests were needed to find the closest hit of
+/// each ray. Rays that needed `max_tests` tests or more are white.
+pub struct TraversalCost {
+    max_tests: usize,
+}
+
+impl TraversalCost {
+    pub fn new(max_tests: usize) -> TraversalCost {
+        TraversalCost { max_tests }
+    }
+}
+
+impl Integrator for TraversalCost {
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color {
+        let tests = world.intersection_tests(ray, &HIT_RANGE);
+        grayscale(tests as f64, self.max_tests as f64)
+    }
+}
@@ -0,0 +1,83 @@
+use crate::color::Color;
+
+/// Sensitivity (ISO) used by automatic exposure
+const AUTO_ISO: f64 = 100.0;
+
+/// Calibration constant of reflected-light meters, which sets the
+/// luminance that automatic exposure maps to middle gray
+const METER_CALIBRATION: f64 = 12.5;
+
+/// Ratio between the luminance that saturates the sensor and the one given
+/// by its sensitivity, so that highlights have some headroom
+const SATURATION_HEADROOM: f64 = 1.2;
+
+/// Small luminance added to every pixel when averaging their logarithms,
+/// so that black pixels don't make the average zero
+const LOG_AVERAGE_DELTA: f64 = 1e-4;
+
+/// How the light that reaches the film of a `Camera` is turned into pixel
+/// values.
+///
+/// With a physical exposure, radiances in the scene are taken to be in nits
+/// (cd/m²), so the same lights look as bright in any scene photographed with
+/// the same settings. The sun lights a white surface to around 30 000 nits,
+/// and an overcast sky to around 3 000.
+#[derive(Clone, Copy)]
+pub enum Exposure {
+    /// Pixel values are the radiance that reaches the film. This is the
+    /// default.
+    Radiance,
+    /// Exposure of a real camera with the given sensitivity (ISO) and time
+    /// that the shutter stays open in seconds, like 100.0 and 1.0 / 125.0 on
+    /// a sunny day at f/16. The f-number is the one of the camera, so the
+    /// camera needs an aperture: without one, no light gets in and images
+    /// are black.
+    Manual { iso: f64, shutter_time: f64 },
+    /// Exposure chosen from the log-average luminance of the image, as the
+    /// light meter of a camera does, so that the image looks neither too
+    /// dark nor too bright
+    Auto,
+}
+
+impl Exposure {
+    /// Return the exposure value at ISO 100 of these settings with a camera
+    /// of the given f-number, which is higher for brighter scenes, or `None`
+    /// for `Exposure::Radiance`
+    fn ev100(&self, pixels: &[Color], f_number: f64) -> Option<f64> {
+        match *self {
+            Exposure::Radiance => None,
+            Exposure::Manual { iso, shutter_time } => {
+                Some((f_number * f_number / shutter_time * 100.0 / iso).log2())
+            }
+            Exposure::Auto => {
+                let log_average = log_average_luminance(pixels);
+                Some((log_average * AUTO_ISO / METER_CALIBRATION).log2())
+            }
+        }
+    }
+
+    /// Return the factor by which the radiance of `pixels`, taken by a
+    /// camera of the given f-number, is multiplied to get their values,
+    /// after brightening them by `compensation` stops
+    pub(crate) fn scale(&self, pixels: &[Color], f_number: f64, compensation: f64) -> f64 {
+        let scale = match self.ev100(pixels, f_number) {
+            Some(ev100) => 1.0 / (SATURATION_HEADROOM * ev100.exp2()),
+            None => 1.0,
+        };
+        scale * compensation.exp2()
+    }
+}
+
+/// Return the exponential of the average logarithm of the luminance of
+/// `pixels`, which unlike the average luminance isn't dominated by a few
+/// bright pixels
+fn log_average_luminance(pixels: &[Color]) -> f64 {
+    if pixels.is_empty() {
+        return 1.0;
+    }
+    let sum: f64 = pixels
+        .iter()
+        .map(|pixel| (LOG_AVERAGE_DELTA + pixel.luminance().max(0.0)).ln())
+        .sum();
+    (sum / pixels.len() as f64).exp()
+}
@@ -7,10 +7,21 @@ use crate::{material::Material, ray::Ray, vector3::Vec3};
 #[derive(Clone)]
 pub struct HitRecord {
     pub point: Vec3,
+    /// Normal of the surface, which always points against the ray that hit it
     pub normal: Vec3,
+    /// Normal used to shade the surface, which can be perturbed by normal
+    /// and bump maps. It is always on the same side of the surface as `normal`.
+    pub shading_normal: Vec3,
     pub dist: f64,
     pub front_face: bool,
     pub material: Rc<dyn Material>,
+    /// Surface coordinates of the hit point, both in the range [0.0, 1.0]
+    pub u: f64,
+    pub v: f64,
+    /// Derivatives of the hit point with respect to the surface coordinates,
+    /// which are zero if the surface doesn't provide them
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
 }
 
 impl HitRecord {
@@ -21,16 +32,90 @@ impl HitRecord {
         dist: f64,
         ray: &Ray,
         material: Rc<dyn Material>,
+        (u, v): (f64, f64),
     ) -> HitRecord {
         let front_face = ray.direction.dot(normal) < 0.0;
+        let normal = if front_face { normal } else { -normal };
         HitRecord {
             point,
-            normal: if front_face { normal } else { -normal },
+            normal,
+            shading_normal: normal,
             dist,
             front_face,
             material,
+            u,
+            v,
+            dpdu: Vec3::zero(),
+            dpdv: Vec3::zero(),
         }
     }
+
+    /// Set the derivatives of the hit point with respect to the surface
+    /// coordinates
+    pub fn with_tangents(mut self, dpdu: Vec3, dpdv: Vec3) -> HitRecord {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    /// Return the normal that points outside of the surface
+    pub fn outward_normal(&self) -> Vec3 {
+        if self.front_face {
+            self.normal
+        } else {
+            -self.normal
+        }
+    }
+
+    /// Return true if `direction` is on the same side of the surface
+    /// according to both the geometric and the shading normal. Materials
+    /// don't scatter light in other directions, since it would leak through
+    /// the surface.
+    pub fn same_side(&self, direction: Vec3) -> bool {
+        direction.dot(self.normal) * direction.dot(self.shading_normal) > 0.0
+    }
+
+    /// Decide if the surface is there at the point where `ray` hit it,
+    /// according to the opacity of its material. Structures should ignore
+    /// hits that fail this test and look for the next one along the ray, so
+    /// that cutouts also let light through for shadows.
+    ///
+    /// The decision looks random, but it's always the same for the same ray
+    /// and hit point, so that testing a ray again gives the same answer.
+    pub fn passes_alpha_test(&self, ray: &Ray) -> bool {
+        let opacity = self.material.opacity(self);
+        if opacity >= 1.0 {
+            return true;
+        }
+        let (origin, direction) = (ray.origin, ray.direction);
+        let values = [
+            origin.x,
+            origin.y,
+            origin.z,
+            direction.x,
+            direction.y,
+            direction.z,
+            self.u,
+            self.v,
+        ];
+        hash_to_unit(&values) < opacity
+    }
+}
+
+/// Return a number in the range [0.0, 1.0) that looks random, but only
+/// depends on `values`
+fn hash_to_unit(values: &[f64]) -> f64 {
+    // Mix the bits of every value with the finalizer of SplitMix64
+    let mix = |mut bits: u64| {
+        bits = (bits ^ (bits >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        bits = (bits ^ (bits >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        bits ^ (bits >> 31)
+    };
+    let hash = values.iter().fold(0x9e37_79b9_7f4a_7c15, |hash, value| {
+        mix(hash ^ value.to_bits())
+    });
+    // Use the highest 53 bits, as many as fit in the mantissa of an f64
+    (hash >> 11) as f64 / (1_u64 << 53) as f64
 }
 
 /// Trait for 3D structures that can be hitten by a `Ray`
@@ -38,4 +123,28 @@ pub trait Hittable {
     /// Return a `HitRecord` if a given `Ray` hits this structure at a distance
     /// from the ray's origin that is in a given hit range.
     fn hit(&self, ray: &Ray, hit_range: &Range<f64>) -> Option<HitRecord>;
+
+    /// Return how many ray-primitive intersection tests are needed to find
+    /// the closest hit of a given `Ray` in a given hit range.
+    fn intersection_tests(&self, _ray: &Ray, _hit_range: &Range<f64>) -> usize {
+        1
+    }
+
+    /// Add to `lights` every structure inside this one that emits light
+    fn collect_lights<'a>(&'a self, _lights: &mut Vec<&'a dyn Hittable>) {}
+
+    /// Return the area of the surface of this structure
+    fn area(&self) -> f64 {
+        0.0
+    }
+
+    /// Return a `HitRecord` for a random point of this structure's surface,
+    /// where all points are equally likely to be chosen. The record is the
+    /// same as if the point had been hit by a ray that hits the front face.
+    /// Points where the surface is cut out should be discarded with a
+    /// probability of 1.0 minus the opacity of the material, returning
+    /// `None`, so that no light is emitted through holes.
+    fn sample_surface(&self) -> Option<HitRecord> {
+        None
+    }
 }
@@ -44,6 +44,26 @@ impl Hittable for HittableList {
         }
         closest_hit
     }
+
+    fn intersection_tests(&self, ray: &Ray, hit_range: &Range<f64>) -> usize {
+        let mut tests = 0;
+        let mut closest_hit_dist = hit_range.end;
+
+        for obj in &self.objects {
+            let new_range = hit_range.start..closest_hit_dist;
+            tests += obj.intersection_tests(ray, &new_range);
+            if let Some(hit) = obj.hit(ray, &new_range) {
+                closest_hit_dist = hit.dist;
+            }
+        }
+        tests
+    }
+
+    fn collect_lights<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        for obj in &self.objects {
+            obj.collect_lights(lights);
+        }
+    }
 }
 
 impl Default for HittableList {
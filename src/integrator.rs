@@ -0,0 +1,211 @@
+use crate::{
+    color,
+    color::Color,
+    hittable::Hittable,
+    ray::Ray,
+    spectrum::{SampledSpectrum, SampledWavelengths},
+    subsurface::RandomWalk,
+    vector3::Vec3,
+};
+
+/// Trait for the algorithms that compute how much light arrives at the
+/// camera along a given `Ray`
+pub trait Integrator {
+    /// Return the radiance carried by `ray` after it travels through `world`
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color;
+
+    /// Same as `radiance`, but integrators that also find light arriving to
+    /// other points of the film (like light tracing does) add it to `splats`
+    fn radiance_and_splats(
+        &self,
+        ray: &Ray,
+        world: &dyn Hittable,
+        _splats: &mut Vec<Splat>,
+    ) -> Color {
+        self.radiance(ray, world)
+    }
+}
+
+/// Light that arrives at the point `(s, t)` of the film, using the same
+/// coordinates as `Camera::get_ray`
+pub struct Splat {
+    pub s: f64,
+    pub t: f64,
+    pub color: Color,
+}
+
+/// State of a path while it is being traced through the scene
+struct PathState {
+    /// Ray that will be traced in the next bounce
+    ray: Ray,
+    /// Product of the attenuations of every bounce done so far
+    throughput: Color,
+    /// Light gathered so far
+    radiance: Color,
+    /// Number of bounces done so far
+    depth: u32,
+    /// Walk through the medium inside the object where the path is, if any
+    medium: Option<RandomWalk>,
+    /// Number of times the path was scattered inside media
+    medium_events: u32,
+}
+
+impl PathState {
+    /// Create the state of a path that starts with the given camera ray
+    fn new(ray: Ray) -> PathState {
+        PathState {
+            ray,
+            throughput: color!(1.0, 1.0, 1.0),
+            radiance: color!(0.0, 0.0, 0.0),
+            depth: 0,
+            medium: None,
+            medium_events: 0,
+        }
+    }
+}
+
+/// Unidirectional path tracer. Rays bounce around the scene until they are
+/// absorbed, escape to the background or reach the maximum depth.
+pub struct PathTracer {
+    max_depth: u32,
+}
+
+impl PathTracer {
+    /// Maximum number of times that a path can be scattered inside media.
+    /// These don't count as bounces, since light can be scattered hundreds
+    /// of times inside translucent objects.
+    const MAX_MEDIUM_EVENTS: u32 = 1000;
+
+    /// Create a path tracer that follows each path for at most `max_depth` bounces
+    pub fn new(max_depth: u32) -> PathTracer {
+        PathTracer { max_depth }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color {
+        let desired_hit_distance = 0.001..f64::INFINITY;
+        let mut path = PathState::new(*ray);
+
+        while path.depth < self.max_depth {
+            let hit = world.hit(&path.ray, &desired_hit_distance);
+
+            // Inside a medium, light may be scattered before reaching the
+            // next surface
+            if let Some(walk) = &mut path.medium {
+                let max_distance = hit.as_ref().map_or(f64::INFINITY, |hit| hit.dist);
+                if let Some(distance) = walk.sample(max_distance) {
+                    path.ray = Ray::new(path.ray.at(distance), Vec3::random_unit_vec());
+                    path.medium_events += 1;
+                    if path.medium_events > PathTracer::MAX_MEDIUM_EVENTS {
+                        return path.radiance;
+                    }
+                    continue;
+                }
+                path.throughput = path.throughput * walk.finish();
+            }
+
+            let hit = match hit {
+                Some(hit) => hit,
+                None => return path.radiance + path.throughput * background(&path.ray),
+            };
+
+            path.radiance += path.throughput * hit.material.emitted(&hit);
+
+            match hit.material.scatter(&path.ray, &hit) {
+                Some((scattered_ray, attenuation)) => {
+                    // Light that goes through the surface of an object with a
+                    // medium inside enters or leaves that medium
+                    if let Some(medium) = hit.material.medium() {
+                        if scattered_ray.direction.dot(hit.normal) < 0.0 {
+                            path.medium = hit.front_face.then(|| medium.start_walk());
+                        }
+                    }
+                    path.throughput = path.throughput * attenuation;
+                    path.ray = scattered_ray;
+                    path.depth += 1;
+                }
+                None => return path.radiance,
+            }
+        }
+        path.radiance
+    }
+}
+
+/// Path tracer that renders in spectral mode: each path carries light of a
+/// few wavelengths instead of an RGB color, and materials whose refraction
+/// index depends on the wavelength split white light into its colors.
+///
+/// Colors of materials, lights and the background are converted to spectra
+/// when they are used, and the light of each path is converted back to a
+/// color with the CIE color matching functions.
+pub struct SpectralPathTracer {
+    max_depth: u32,
+}
+
+impl SpectralPathTracer {
+    /// Create a spectral path tracer that follows each path for at most
+    /// `max_depth` bounces
+    pub fn new(max_depth: u32) -> SpectralPathTracer {
+        SpectralPathTracer { max_depth }
+    }
+}
+
+impl Integrator for SpectralPathTracer {
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color {
+        let desired_hit_distance = 0.001..f64::INFINITY;
+        let mut wavelengths = SampledWavelengths::sample();
+        let mut ray = ray.with_wavelength(wavelengths.hero());
+        let mut throughput = SampledSpectrum::constant(1.0);
+        let mut radiance = SampledSpectrum::constant(0.0);
+
+        for _ in 0..self.max_depth {
+            let hit = match world.hit(&ray, &desired_hit_distance) {
+                Some(hit) => hit,
+                None => {
+                    let background = SampledSpectrum::from_rgb(background(&ray), &wavelengths);
+                    radiance += throughput * background;
+                    break;
+                }
+            };
+
+            let emitted = hit.material.emitted(&hit);
+            if !emitted.is_black() {
+                radiance += throughput * SampledSpectrum::from_rgb(emitted, &wavelengths);
+            }
+
+            // Only the hero wavelength follows the path chosen by a
+            // dispersive material
+            if hit.material.is_dispersive() {
+                wavelengths.terminate_secondary();
+            }
+
+            match hit.material.scatter(&ray, &hit) {
+                Some((scattered_ray, attenuation)) => {
+                    throughput = throughput * SampledSpectrum::from_rgb(attenuation, &wavelengths);
+                    if throughput.is_black() {
+                        break;
+                    }
+                    ray = scattered_ray.with_wavelength(wavelengths.hero());
+                }
+                None => break,
+            }
+        }
+        radiance.to_rgb(&wavelengths)
+    }
+}
+
+/// Return the background color
+pub fn background(ray: &Ray) -> Color {
+    // We generate a white-blue gradient based on the 'y' coordinate.
+    // The higher the 'y', the 'bluer' the pixel.
+    // The lower the 'y', the whiter the pixel.
+    // Since Rays use unit vectors for directions, it will be blue
+    // the most on the middle-top of the image
+    let t = 0.5 * (ray.direction.y + 1.0);
+    let white = color!(1.0, 1.0, 1.0);
+    let blue = color!(0.5, 0.7, 1.0);
+
+    // This is called a 'linear interpolation'
+    (1.0 - t) * white + t * blue
+}
@@ -0,0 +1,100 @@
+use crate::vector3::Vec3;
+
+/// Return the coordinate of `point` in the given axis (0 = X, 1 = Y, 2 = Z)
+fn coordinate(point: Vec3, axis: usize) -> f64 {
+    match axis {
+        0 => point.x,
+        1 => point.y,
+        _ => point.z,
+    }
+}
+
+/// Balanced tree of items placed at points in space, which can quickly find
+/// every item close to a given point.
+///
+/// The tree is stored in a single `Vec`: the root of the items in a range of
+/// indices is the item in the middle of the range, the items before it belong
+/// to its left subtree and the ones after it to its right subtree.
+pub struct KdTree<T> {
+    items: Vec<(Vec3, T)>,
+    /// Axis in which each node splits its subtrees
+    split_axes: Vec<usize>,
+}
+
+impl<T> KdTree<T> {
+    /// Create a tree with the given items
+    pub fn new(mut items: Vec<(Vec3, T)>) -> KdTree<T> {
+        let mut split_axes = vec![0; items.len()];
+        KdTree::build(&mut items, &mut split_axes);
+        KdTree { items, split_axes }
+    }
+
+    /// Sort `items` so that they form a tree, splitting them by the axis in
+    /// which they are more spread out
+    fn build(items: &mut [(Vec3, T)], split_axes: &mut [usize]) {
+        if items.len() <= 1 {
+            return;
+        }
+
+        let extent = |axis| {
+            let coords = items.iter().map(|(point, _)| coordinate(*point, axis));
+            let min = coords.clone().fold(f64::INFINITY, f64::min);
+            let max = coords.fold(f64::NEG_INFINITY, f64::max);
+            max - min
+        };
+        let axis = (0..3)
+            .max_by(|&a, &b| extent(a).total_cmp(&extent(b)))
+            .unwrap();
+
+        let middle = items.len() / 2;
+        items.select_nth_unstable_by(middle, |(a, _), (b, _)| {
+            coordinate(*a, axis).total_cmp(&coordinate(*b, axis))
+        });
+        split_axes[middle] = axis;
+
+        let (left_items, right_items) = items.split_at_mut(middle);
+        let (left_axes, right_axes) = split_axes.split_at_mut(middle);
+        KdTree::build(left_items, left_axes);
+        KdTree::build(&mut right_items[1..], &mut right_axes[1..]);
+    }
+
+    /// Call `f` with every item whose distance to `center` is `radius` or less
+    pub fn for_each_within<F>(&self, center: Vec3, radius: f64, mut f: F)
+    where
+        F: FnMut(Vec3, &T),
+    {
+        self.visit(0..self.items.len(), center, radius * radius, &mut f);
+    }
+
+    fn visit<F>(&self, range: std::ops::Range<usize>, center: Vec3, radius_squared: f64, f: &mut F)
+    where
+        F: FnMut(Vec3, &T),
+    {
+        if range.is_empty() {
+            return;
+        }
+
+        let middle = range.start + range.len() / 2;
+        let (point, item) = &self.items[middle];
+        if (*point - center).length_squared() <= radius_squared {
+            f(*point, item);
+        }
+
+        // Visit first the subtree where `center` is, and only visit the
+        // other one if the sphere around `center` reaches it
+        let axis = self.split_axes[middle];
+        let dist_to_plane = coordinate(center, axis) - coordinate(*point, axis);
+        let left = range.start..middle;
+        let right = middle + 1..range.end;
+        let (near, far) = if dist_to_plane < 0.0 {
+            (left, right)
+        } else {
+            (right, left)
+        };
+
+        self.visit(near, center, radius_squared, f);
+        if dist_to_plane * dist_to_plane <= radius_squared {
+            self.visit(far, center, radius_squared, f);
+        }
+    }
+}
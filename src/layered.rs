@@ -0,0 +1,212 @@
+use std::rc::Rc;
+
+use crate::{
+    color,
+    color::Color,
+    hittable::HitRecord,
+    material::{Material, RoughDielectric},
+    ray::Ray,
+    subsurface::Medium,
+    vector3::Vec3,
+};
+
+/// Material made of a dielectric coat over any other material, like varnish
+/// over wood or the clear coat of car paint.
+///
+/// Light that goes through the coat can bounce between the base and the
+/// inside of the coat any number of times before leaving, and `scatter`
+/// follows it through each of those bounces, so that no energy is lost or
+/// created between layers. The coat is assumed to be so thin that light
+/// leaves it at the same point where it entered. Light that a transmissive
+/// base lets through leaves the coat into the inside of the object, where
+/// it travels through the medium of the base, if any.
+///
+/// Since the light that leaves the material in a direction can come from any
+/// number of bounces, its BSDF can't be evaluated, so like specular
+/// materials, it can only be sampled with `scatter`.
+pub struct Layered {
+    base: Rc<dyn Material>,
+    coat: RoughDielectric,
+    /// Color of the light that goes straight through the coat
+    tint: Color,
+}
+
+impl Layered {
+    /// Maximum number of times that light can go through the coat and back
+    /// before it is considered absorbed
+    const MAX_BOUNCES: u32 = 32;
+
+    /// Cover `base` with a coat with a given refraction index and a
+    /// roughness in the range [0.0, 1.0]
+    pub fn new(base: Rc<dyn Material>, refraction_idx: f64, roughness: f64) -> Layered {
+        Layered {
+            base,
+            coat: RoughDielectric::new(refraction_idx, roughness),
+            tint: color!(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Tint the coat, so that only `color` of the light remains after going
+    /// straight through it. Light that goes through it at an angle is tinted
+    /// more, since it travels a longer distance inside it.
+    pub fn with_tint(mut self, color: Color) -> Layered {
+        self.tint = color;
+        self
+    }
+
+    /// Return the fraction of light that remains after going through the
+    /// coat in a given direction
+    fn coat_transmittance(&self, direction: Vec3, normal: Vec3) -> Color {
+        let distance = 1.0 / direction.dot(normal).abs().max(1e-3);
+        color!(
+            self.tint.red.powf(distance),
+            self.tint.green.powf(distance),
+            self.tint.blue.powf(distance)
+        )
+    }
+}
+
+impl Material for Layered {
+    fn scatter(&self, ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
+        // Light coming from inside the object never reaches the coat
+        if !hit.front_face {
+            return self.base.scatter(ray_in, hit);
+        }
+        let normal = hit.shading_normal;
+
+        // Record of `ray` hitting the base or the bottom of the coat, which
+        // are both at the hit point, in the direction of the ray
+        let layer_hit = |ray: &Ray| {
+            let front_face = ray.direction.dot(normal) < 0.0;
+            let flip = |v: Vec3| if front_face == hit.front_face { v } else { -v };
+            HitRecord {
+                normal: flip(hit.normal),
+                shading_normal: flip(hit.shading_normal),
+                dist: 0.0,
+                front_face,
+                ..hit.clone()
+            }
+        };
+
+        let (mut ray, mut weight) = self.coat.scatter(ray_in, hit)?;
+        if ray.direction.dot(normal) > 0.0 {
+            // Reflected by the top of the coat
+            return Some((ray, weight));
+        }
+
+        for _ in 0..Layered::MAX_BOUNCES {
+            // Go down through the coat and scatter on the base
+            weight = weight * self.coat_transmittance(ray.direction, normal);
+            let (scattered_ray, attenuation) = self.base.scatter(&ray, &layer_hit(&ray))?;
+            if scattered_ray.direction.dot(normal) <= 0.0 {
+                // Transmitted by the base into the inside of the object
+                let direction = scattered_ray.direction;
+                return (direction.dot(hit.normal) < 0.0)
+                    .then(|| (Ray::new(hit.point, direction), weight * attenuation));
+            }
+            weight = weight * attenuation;
+            ray = scattered_ray;
+
+            // Go up through the coat, and either leave the material or be
+            // reflected back to the base
+            weight = weight * self.coat_transmittance(ray.direction, normal);
+            let (scattered_ray, attenuation) = self.coat.scatter(&ray, &layer_hit(&ray))?;
+            weight = weight * attenuation;
+            if scattered_ray.direction.dot(normal) > 0.0 {
+                let direction = scattered_ray.direction;
+                return hit
+                    .same_side(direction)
+                    .then(|| (Ray::new(hit.point, direction), weight));
+            }
+            ray = scattered_ray;
+
+            if weight.is_black() {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.base.medium()
+    }
+
+    fn opacity(&self, hit: &HitRecord) -> f64 {
+        self.base.opacity(hit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::{material::Dielectric, subsurface::Subsurface, vec3};
+
+    /// Material that reflects light straight up and records the derivatives
+    /// of the hits that it scatters
+    struct Probe {
+        dpdu: Cell<Vec3>,
+    }
+
+    impl Material for Probe {
+        fn scatter(&self, _ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
+            self.dpdu.set(hit.dpdu);
+            Some((Ray::new(hit.point, hit.normal), color!(1.0, 1.0, 1.0)))
+        }
+    }
+
+    fn hit_from_above(material: Rc<dyn Material>) -> (Ray, HitRecord) {
+        let ray = Ray::new(vec3!(0.0, 1.0, 0.0), vec3!(0.2, -1.0, 0.0));
+        let mut hit = HitRecord::new(
+            Vec3::zero(),
+            vec3!(0.0, 1.0, 0.0),
+            1.0,
+            &ray,
+            material,
+            (0.5, 0.5),
+        );
+        hit.dpdu = vec3!(1.0, 0.0, 0.0);
+        hit.dpdv = vec3!(0.0, 0.0, 1.0);
+        (ray, hit)
+    }
+
+    #[test]
+    fn base_sees_the_whole_hit() {
+        let probe = Rc::new(Probe {
+            dpdu: Cell::new(Vec3::zero()),
+        });
+        let layered = Rc::new(Layered::new(probe.clone(), 1.5, 0.0));
+        let (ray, hit) = hit_from_above(layered.clone());
+        // The coat reflects only a few percent of the light, so some of the
+        // rays reach the base
+        for _ in 0..100 {
+            layered.scatter(&ray, &hit);
+        }
+        assert!((probe.dpdu.get() - hit.dpdu).length() < 1e-12);
+    }
+
+    #[test]
+    fn transmissive_bases_let_light_into_the_object() {
+        let base = Rc::new(Dielectric::new(1.5));
+        let layered = Rc::new(Layered::new(base, 1.5, 0.0));
+        let (ray, hit) = hit_from_above(layered.clone());
+        let transmitted = (0..1000)
+            .filter_map(|_| layered.scatter(&ray, &hit))
+            .filter(|(scattered, weight)| scattered.direction.y < 0.0 && !weight.is_black())
+            .count();
+        assert!(transmitted > 500);
+    }
+
+    #[test]
+    fn forwards_the_medium_of_the_base() {
+        let base = Rc::new(Subsurface::new(
+            color!(0.8, 0.8, 0.8),
+            color!(1.0, 1.0, 1.0),
+            1.3,
+        ));
+        assert!(Layered::new(base, 1.5, 0.2).medium().is_some());
+        let base = Rc::new(Dielectric::new(1.5));
+        assert!(Layered::new(base, 1.5, 0.2).medium().is_none());
+    }
+}
@@ -0,0 +1,487 @@
+use std::{io, path::Path};
+
+use crate::{
+    ray::Ray,
+    rtweekend::{random_num, PI},
+    vec3,
+    vector3::Vec3,
+};
+
+/// Spherical (or flat, for the aperture stop) surface between two media of a
+/// lens system. Lengths are in meters.
+#[derive(Clone, Copy)]
+struct LensElement {
+    /// Radius of curvature, which is positive when the center of the sphere
+    /// is on the side of the film, and 0.0 for the aperture stop
+    radius: f64,
+    /// Distance along the optical axis to the next surface, or to the film
+    /// for the last one
+    thickness: f64,
+    /// Refraction index of the medium between this surface and the next one
+    refraction_idx: f64,
+    aperture_radius: f64,
+}
+
+/// System of lenses of a real camera, described by its prescription: the list
+/// of its surfaces from the front (the side of the scene) to the back (the
+/// side of the film).
+///
+/// Rays are traced through each surface of the system, so images show the
+/// distortion, vignetting and depth of field of the real lens.
+pub struct LensSystem {
+    elements: Vec<LensElement>,
+    /// Diagonal of the film, in meters
+    film_diagonal: f64,
+}
+
+impl LensSystem {
+    /// Number of times the radius of the entrance pupil is bisected to find
+    /// the f-number
+    const PUPIL_BISECTIONS: usize = 32;
+
+    /// Load a lens prescription from a text file. See `LensSystem::parse`
+    /// for its format.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<LensSystem> {
+        LensSystem::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parse a lens prescription, in the format used by pbrt: each line has
+    /// the radius of curvature, thickness, refraction index and aperture
+    /// diameter of a surface, in millimeters. Surfaces go from the front of
+    /// the lens to the back, and the aperture stop is a surface with radius
+    /// 0. A refraction index of 0 means air. Everything after a '#' is a
+    /// comment.
+    ///
+    /// Return an `InvalidData` error if a value isn't a finite number, if a
+    /// thickness, refraction index or aperture is negative (or the aperture
+    /// 0), or if there's no aperture stop.
+    ///
+    /// The film is 35 mm (full frame) by default.
+    pub fn parse(text: &str) -> io::Result<LensSystem> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut elements = vec![];
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            if line.trim().is_empty() {
+                continue;
+            }
+            let values = line
+                .split_whitespace()
+                .map(|value| value.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| invalid(format!("line {}: {err}", idx + 1)))?;
+            let [radius, thickness, refraction_idx, aperture] = values[..] else {
+                return Err(invalid(format!("line {}: expected 4 numbers", idx + 1)));
+            };
+            if !values.iter().all(|value| value.is_finite()) {
+                return Err(invalid(format!(
+                    "line {}: expected finite numbers",
+                    idx + 1
+                )));
+            }
+            if thickness < 0.0 || aperture <= 0.0 || refraction_idx < 0.0 {
+                return Err(invalid(format!(
+                    "line {}: thickness, refraction index and aperture can't be negative, \
+                     and the aperture can't be 0",
+                    idx + 1
+                )));
+            }
+            elements.push(LensElement {
+                radius: radius / 1000.0,
+                thickness: thickness / 1000.0,
+                refraction_idx: if refraction_idx == 0.0 {
+                    1.0
+                } else {
+                    refraction_idx
+                },
+                aperture_radius: aperture / 2000.0,
+            });
+        }
+        if elements.is_empty() {
+            return Err(invalid("empty lens prescription".to_string()));
+        }
+        if !elements.iter().any(|element| element.radius == 0.0) {
+            return Err(invalid(
+                "no aperture stop (surface with radius 0)".to_string(),
+            ));
+        }
+
+        Ok(LensSystem {
+            elements,
+            film_diagonal: 0.04327,
+        })
+    }
+
+    /// Set the diagonal of the film, in millimeters
+    pub fn with_film_diagonal(mut self, diagonal: f64) -> LensSystem {
+        self.film_diagonal = diagonal / 1000.0;
+        self
+    }
+
+    /// Return the effective focal length of the lens system in meters, or
+    /// `None` if light parallel to its axis doesn't get through it
+    pub(crate) fn focal_length(&self) -> Option<f64> {
+        let x = 0.001 * self.film_diagonal;
+        let front = element_position(&self.elements, 0);
+        let from_scene = Ray::new(vec3!(x, 0.0, front + 1.0), vec3!(0.0, 0.0, -1.0));
+        let out = trace(&self.elements, from_scene, false)?;
+        let (principal_plane, focal_point) = cardinal_points(&from_scene, &out);
+        Some(principal_plane - focal_point)
+    }
+
+    /// Return the f-number of the lens system, which is its focal length
+    /// divided by the diameter of its entrance pupil: the widest beam of
+    /// light parallel to the axis that gets through it
+    pub(crate) fn f_number(&self) -> Option<f64> {
+        let front = element_position(&self.elements, 0);
+        let gets_through = |x: f64| {
+            let ray = Ray::new(vec3!(x, 0.0, front + 1.0), vec3!(0.0, 0.0, -1.0));
+            trace(&self.elements, ray, false).is_some()
+        };
+
+        // Bisect the radius of the entrance pupil, which is never larger than
+        // the front surface
+        let (mut inside, mut outside) = (0.0, self.elements[0].aperture_radius);
+        for _ in 0..Self::PUPIL_BISECTIONS {
+            let middle = (inside + outside) / 2.0;
+            if gets_through(middle) {
+                inside = middle;
+            } else {
+                outside = middle;
+            }
+        }
+        Some(self.focal_length()? / (2.0 * inside))
+    }
+
+    /// Move the lens so that points at `focus_distance` (in meters, from the
+    /// film) are in focus, with a film of a given aspect ratio
+    pub(crate) fn focus(&self, focus_distance: f64, aspect_ratio: f64) -> FocusedLens {
+        let mut elements = self.elements.clone();
+        let film_height = self.film_diagonal / (aspect_ratio * aspect_ratio + 1.0).sqrt();
+
+        // Thick lens approximation of the system, from two rays parallel to
+        // the axis near it: one from the scene and one from the film
+        let x = 0.001 * self.film_diagonal;
+        let front = element_position(&elements, 0);
+        let from_scene = Ray::new(vec3!(x, 0.0, front + 1.0), vec3!(0.0, 0.0, -1.0));
+        let from_film = Ray::new(vec3!(x, 0.0, -1.0), vec3!(0.0, 0.0, 1.0));
+        let film_side =
+            trace(&elements, from_scene, false).map(|out| cardinal_points(&from_scene, &out));
+        let scene_side =
+            trace(&elements, from_film, true).map(|out| cardinal_points(&from_film, &out));
+
+        if let (Some((film_principal, film_focal)), Some((scene_principal, _))) =
+            (film_side, scene_side)
+        {
+            // Find how far the lens has to move away from the film so that
+            // 1 / object distance + 1 / image distance = 1 / focal length
+            let focal_length = film_principal - film_focal;
+            let k = focus_distance - scene_principal + film_principal;
+            let discriminant = k * k - 4.0 * k * focal_length;
+            let image_distance = if discriminant >= 0.0 {
+                (k - discriminant.sqrt()) / 2.0
+            } else {
+                // Too close to focus, so focus as close as possible
+                k / 2.0
+            };
+            let last = elements.len() - 1;
+            elements[last].thickness += image_distance - film_principal;
+        }
+
+        let mut lens = FocusedLens {
+            elements,
+            film_width: aspect_ratio * film_height,
+            film_height,
+            pupil_bounds: vec![],
+            reference_exposure: 0.0,
+        };
+        lens.compute_exit_pupil();
+        lens
+    }
+}
+
+/// Return the position along the optical axis of the surface `idx`, where
+/// the film is at 0.0 and the scene is on the positive side
+fn element_position(elements: &[LensElement], idx: usize) -> f64 {
+    elements[idx..]
+        .iter()
+        .map(|element| element.thickness)
+        .sum()
+}
+
+/// Trace a ray through the lens system, either from the film to the scene or
+/// the other way around. Return the ray that leaves the system, or `None` if
+/// it's blocked by it.
+fn trace(elements: &[LensElement], mut ray: Ray, from_film: bool) -> Option<Ray> {
+    let order: Vec<usize> = if from_film {
+        (0..elements.len()).rev().collect()
+    } else {
+        (0..elements.len()).collect()
+    };
+
+    for idx in order {
+        let element = elements[idx];
+        let position = element_position(elements, idx);
+        let (point, normal) = if element.radius == 0.0 {
+            // The aperture stop is flat, and light goes straight through it
+            let dist = (position - ray.origin.z) / ray.direction.z;
+            (ray.at(dist), None)
+        } else {
+            let center = vec3!(0.0, 0.0, position - element.radius);
+            let dist = hit_sphere(&ray, center, element.radius, position)?;
+            let point = ray.at(dist);
+            (point, Some((point - center).unit_vec()))
+        };
+        if point.x * point.x + point.y * point.y > element.aperture_radius.powi(2) {
+            return None;
+        }
+
+        let direction = match normal {
+            Some(normal) => {
+                // Media on the side of the film and of the scene
+                let film_side = element.refraction_idx;
+                let scene_side = match idx {
+                    0 => 1.0,
+                    _ => elements[idx - 1].refraction_idx,
+                };
+                let refraction_ratio = if from_film {
+                    film_side / scene_side
+                } else {
+                    scene_side / film_side
+                };
+                let direction = ray.direction.unit_vec();
+                let normal = if normal.dot(direction) > 0.0 {
+                    -normal
+                } else {
+                    normal
+                };
+                let cos_theta = -direction.dot(normal);
+                let sin_theta_squared = 1.0 - cos_theta * cos_theta;
+                if refraction_ratio * refraction_ratio * sin_theta_squared > 1.0 {
+                    // Total internal reflection
+                    return None;
+                }
+                direction.refract(normal, refraction_ratio)
+            }
+            None => ray.direction,
+        };
+        ray = Ray::new(point, direction);
+    }
+    Some(ray)
+}
+
+/// Return the distance along `ray` to the hit with the part of a sphere that
+/// is closest to the point of the optical axis at `position`
+fn hit_sphere(ray: &Ray, center: Vec3, radius: f64, position: f64) -> Option<f64> {
+    let oc = ray.origin - center;
+    let a = ray.direction.length_squared();
+    let half_b = ray.direction.dot(oc);
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrt_d = discriminant.sqrt();
+    [(-half_b - sqrt_d) / a, (-half_b + sqrt_d) / a]
+        .into_iter()
+        .filter(|&dist| dist > 0.0)
+        .min_by(|&a, &b| {
+            let distance_to_vertex = |dist: f64| (ray.at(dist).z - position).abs();
+            distance_to_vertex(a).total_cmp(&distance_to_vertex(b))
+        })
+}
+
+/// Return the positions along the optical axis of the principal plane and
+/// the focal point, given a ray parallel to the axis and the ray that leaves
+/// the lens system after tracing it
+fn cardinal_points(ray_in: &Ray, ray_out: &Ray) -> (f64, f64) {
+    let focal_dist = -ray_out.origin.x / ray_out.direction.x;
+    let focal_point = ray_out.origin.z + focal_dist * ray_out.direction.z;
+    let principal_dist = (ray_in.origin.x - ray_out.origin.x) / ray_out.direction.x;
+    let principal_plane = ray_out.origin.z + principal_dist * ray_out.direction.z;
+    (principal_plane, focal_point)
+}
+
+/// Rectangle of the plane of the rear surface of a lens system
+#[derive(Clone, Copy)]
+struct Bounds {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl Bounds {
+    fn area(&self) -> f64 {
+        (self.max.0 - self.min.0) * (self.max.1 - self.min.1)
+    }
+}
+
+/// Lens system moved to focus at a given distance, ready to trace rays
+pub(crate) struct FocusedLens {
+    elements: Vec<LensElement>,
+    film_width: f64,
+    film_height: f64,
+    /// Bounds of the exit pupil (the part of the rear surface through which
+    /// light can reach the scene) seen from points of the film along the X
+    /// axis, for equally wide intervals of distance to the center
+    pupil_bounds: Vec<Option<Bounds>>,
+    /// Light that reaches the center of the film, used to normalize the
+    /// weight of the rays
+    reference_exposure: f64,
+}
+
+impl FocusedLens {
+    /// Number of intervals of distance to the center of the film whose exit
+    /// pupil is computed
+    const PUPIL_INTERVALS: usize = 64;
+    /// Number of samples along each side of the rear surface used to find the
+    /// exit pupil
+    const PUPIL_SAMPLES: usize = 64;
+
+    fn rear_element(&self) -> (f64, f64) {
+        let last = self.elements.len() - 1;
+        (
+            element_position(&self.elements, last),
+            self.elements[last].aperture_radius,
+        )
+    }
+
+    fn max_film_radius(&self) -> f64 {
+        (self.film_width * self.film_width + self.film_height * self.film_height).sqrt() / 2.0
+    }
+
+    /// Call `f` with every point of a grid over the rear surface through
+    /// which light from `film_point` reaches the scene, and the cosine of the
+    /// angle between that light and the optical axis
+    fn for_each_pupil_point(&self, film_point: Vec3, mut f: impl FnMut(f64, f64, f64)) {
+        let (rear_position, rear_radius) = self.rear_element();
+        let size = 1.5 * rear_radius;
+        let samples = FocusedLens::PUPIL_SAMPLES;
+        for i in 0..samples * samples {
+            let x = -size + 2.0 * size * ((i % samples) as f64 + 0.5) / samples as f64;
+            let y = -size + 2.0 * size * ((i / samples) as f64 + 0.5) / samples as f64;
+            let direction = vec3!(x, y, rear_position) - film_point;
+            if trace(&self.elements, Ray::new(film_point, direction), true).is_some() {
+                f(x, y, direction.z / direction.length());
+            }
+        }
+    }
+
+    /// Find the bounds of the exit pupil for points of the film at any
+    /// distance from the center, and the light that reaches the center
+    fn compute_exit_pupil(&mut self) {
+        let (_, rear_radius) = self.rear_element();
+        let cell_size = 3.0 * rear_radius / FocusedLens::PUPIL_SAMPLES as f64;
+        let intervals = FocusedLens::PUPIL_INTERVALS;
+        let max_radius = self.max_film_radius();
+
+        self.pupil_bounds = (0..intervals)
+            .map(|interval| {
+                let mut bounds: Option<Bounds> = None;
+                // Points at the start, middle and end of the interval
+                for step in 0..3 {
+                    let film_x =
+                        max_radius * (interval as f64 + step as f64 / 2.0) / intervals as f64;
+                    self.for_each_pupil_point(vec3!(film_x, 0.0, 0.0), |x, y, _| {
+                        let b = bounds.get_or_insert(Bounds {
+                            min: (x, y),
+                            max: (x, y),
+                        });
+                        b.min = (b.min.0.min(x), b.min.1.min(y));
+                        b.max = (b.max.0.max(x), b.max.1.max(y));
+                    });
+                }
+                // Grow the bounds by one cell, since the pupil can reach the
+                // next sample
+                bounds.map(|b| Bounds {
+                    min: (b.min.0 - cell_size, b.min.1 - cell_size),
+                    max: (b.max.0 + cell_size, b.max.1 + cell_size),
+                })
+            })
+            .collect();
+
+        let mut exposure = 0.0;
+        self.for_each_pupil_point(Vec3::zero(), |_, _, cos_theta| {
+            exposure += cos_theta.powi(4) * cell_size * cell_size;
+        });
+        self.reference_exposure = exposure;
+    }
+
+    /// Return a ray that leaves the lens towards the scene, for light that
+    /// arrives at the film coordinates `(s, t)`, and its weight. The weight
+    /// is 1.0 for rays that reach the center of the film and decreases
+    /// towards the edges, as less light gets through the lens there. Rays
+    /// are in the space of the lens, where the film is centered at the origin
+    /// and the scene is towards positive Z.
+    pub(crate) fn sample_ray(&self, s: f64, t: f64) -> Option<(Ray, f64)> {
+        // The lens flips the image, so the film is flipped too
+        let film_point = vec3!(
+            -(s - 0.5) * self.film_width,
+            -(t - 0.5) * self.film_height,
+            0.0
+        );
+        let film_radius = (film_point.x * film_point.x + film_point.y * film_point.y).sqrt();
+        let interval =
+            (film_radius / self.max_film_radius() * FocusedLens::PUPIL_INTERVALS as f64) as usize;
+        let bounds = (*self
+            .pupil_bounds
+            .get(interval.min(FocusedLens::PUPIL_INTERVALS - 1))?)?;
+
+        // Pupil bounds are computed for points along the X axis, so they are
+        // rotated to the direction of the film point
+        let x = bounds.min.0 + random_num() * (bounds.max.0 - bounds.min.0);
+        let y = bounds.min.1 + random_num() * (bounds.max.1 - bounds.min.1);
+        let angle = if film_radius > 0.0 {
+            film_point.y.atan2(film_point.x)
+        } else {
+            2.0 * PI * random_num()
+        };
+        let (sin, cos) = angle.sin_cos();
+        let (rear_position, _) = self.rear_element();
+        let pupil_point = vec3!(x * cos - y * sin, x * sin + y * cos, rear_position);
+
+        let direction = pupil_point - film_point;
+        let ray = trace(&self.elements, Ray::new(film_point, direction), true)?;
+        let cos_theta = direction.z / direction.length();
+        let weight = cos_theta.powi(4) * bounds.area() / self.reference_exposure;
+        Some((ray, weight))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_gauss_lens_is_50_mm() {
+        let lens = LensSystem::load("lenses/dgauss.50mm.txt").unwrap();
+        let focal_length = lens.focal_length().unwrap();
+        assert!((focal_length - 0.050).abs() < 0.001, "{focal_length} m");
+    }
+
+    #[test]
+    fn double_gauss_lens_is_f_2() {
+        let lens = LensSystem::load("lenses/dgauss.50mm.txt").unwrap();
+        let f_number = lens.f_number().unwrap();
+        assert!((f_number - 2.0).abs() < 0.1, "f/{f_number}");
+    }
+
+    #[test]
+    fn rejects_invalid_prescriptions() {
+        let stop = "0 4.5 0 17.1\n";
+        for text in [
+            "29.475 NaN 1.67 25.2\n",
+            "29.475 3.76 1.67 inf\n",
+            "29.475 -3.76 1.67 25.2\n",
+            "29.475 3.76 1.67 -25.2\n",
+            "29.475 3.76 -1.67 25.2\n",
+        ] {
+            let err = LensSystem::parse(&format!("{text}{stop}")).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{text}");
+        }
+
+        let err = LensSystem::parse("29.475 3.76 1.67 25.2\n").err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(LensSystem::parse(&format!("29.475 3.76 1.67 25.2\n{stop}")).is_ok());
+    }
+}
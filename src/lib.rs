@@ -1,106 +1,207 @@
+mod ambient_occlusion;
+mod angle;
+mod animation;
+mod aperture;
+mod bdpt;
 mod camera;
 mod color;
+mod complex;
+mod cutout;
+pub mod debug_integrators;
+mod exposure;
 mod hittable;
 mod hittable_list;
+mod integrator;
+mod kd_tree;
+mod layered;
+mod lens_system;
+mod lights;
 mod material;
+mod microfacet;
+mod mix;
+mod normal_mapping;
+mod onb;
+mod photon_mapping;
+mod principled;
 mod ray;
+mod rotate;
 mod rtweekend;
+mod spectrum;
 mod sphere;
+mod subsurface;
+mod texture;
+mod thin_film;
 mod vector3;
 
 pub use {
-    camera::Camera,
+    ambient_occlusion::AmbientOcclusion,
+    angle::{Degrees, ParseAngleError, Radians},
+    animation::{CameraPath, FrameSequence, Interpolation, Keyframe},
+    aperture::ApertureShape,
+    bdpt::BidirectionalPathTracer,
+    camera::{
+        Camera, CameraBuilder, Eye, FisheyeMapping, Projection, Stereo, StereoLayout, StereoMode,
+    },
     color::Color,
+    cutout::Cutout,
+    exposure::Exposure,
     hittable::Hittable,
     hittable_list::HittableList,
-    material::{Dielectric, Lambertian, Material, Metal},
+    integrator::{Integrator, PathTracer, SpectralPathTracer, Splat},
+    layered::Layered,
+    lens_system::LensSystem,
+    material::{Conductor, Dielectric, DiffuseLight, Lambertian, Material, Metal, RoughDielectric},
+    mix::{MixMaterial, MixWeight},
+    normal_mapping::{BumpMap, NormalMap},
+    photon_mapping::{PhotonMapping, ProgressivePhotonMapping},
+    principled::Principled,
+    ray::Ray,
+    rotate::Rotate,
     rtweekend::{random_num, random_num_in_range},
+    spectrum::Dispersion,
     sphere::Sphere,
+    subsurface::Subsurface,
+    texture::{Checker, ImageTexture, SolidColor, Texture},
     vector3::Vec3,
 };
 
-use {ray::Ray, rtweekend::clamp};
+use rtweekend::clamp;
 
-use std::fmt::Write;
+use std::{fmt::Write as _, fs::File, io, io::BufWriter, path::Path};
 
+/// Render `world` as seen from `camera` and return it as a PPM image.
+/// The color of each sample is computed by the given `Integrator`.
 pub fn create_image(
     world: &HittableList,
     camera: &Camera,
+    integrator: &dyn Integrator,
     image_width: usize,
     samples_per_pixel: u32,
 ) -> String {
+    let pixels = render(world, camera, integrator, image_width, samples_per_pixel);
+    write_image(&pixels, image_width)
+}
+
+/// Render `world` as seen from a stereo `camera` and return the images of
+/// its left and right eyes as PPM images, or `None` if `camera` isn't a
+/// stereo camera. Both eyes are rendered at once, as in `create_image`, so
+/// integrators that splat light onto the film see the whole stereo image.
+pub fn create_stereo_images(
+    world: &HittableList,
+    camera: &Camera,
+    integrator: &dyn Integrator,
+    image_width: usize,
+    samples_per_pixel: u32,
+) -> Option<(String, String)> {
+    if !camera.is_stereo() {
+        return None;
+    }
+    let pixels = render(world, camera, integrator, image_width, samples_per_pixel);
+    let (left, right, eye_width) = camera.split_eyes(&pixels, image_width)?;
+    Some((
+        write_image(&left, eye_width),
+        write_image(&right, eye_width),
+    ))
+}
+
+/// Render `world` as seen from `camera` and return the color of each pixel,
+/// row by row starting with the bottom one
+fn render(
+    world: &HittableList,
+    camera: &Camera,
+    integrator: &dyn Integrator,
+    image_width: usize,
+    samples_per_pixel: u32,
+) -> Vec<Color> {
     // Image
     let image_height: usize = (image_width as f64 / camera.aspect_ratio()) as usize;
-    let max_depth = 50;
 
-    let mut image = String::new();
+    // Sum of the colors of every sample of each pixel. Pixels are stored row
+    // by row, and the first row is the bottom one.
+    let mut pixels = vec![color!(0.0, 0.0, 0.0); image_width * image_height];
+    let mut splats = vec![];
 
     // Render
-    write!(&mut image, "P3\n{} {}\n255\n", image_width, image_height).unwrap();
-
-    for y in (0..image_height).rev() {
+    for y in 0..image_height {
         for x in 0..image_width {
-            let mut pixel_color = color!(0.0, 0.0, 0.0);
             for _ in 0..samples_per_pixel {
-                let u = ((x as f64) + random_num()) / ((image_width - 1) as f64);
-                let v = ((y as f64) + random_num()) / ((image_height - 1) as f64);
-                let ray = camera.get_ray(u, v);
-                pixel_color += ray_color(&ray, world, max_depth);
+                let u = ((x as f64) + random_num()) / (image_width as f64);
+                let v = ((y as f64) + random_num()) / (image_height as f64);
+                if let Some((ray, weight)) = camera.get_weighted_ray(u, v) {
+                    pixels[y * image_width + x] +=
+                        weight * integrator.radiance_and_splats(&ray, world, &mut splats);
+                }
             }
-            write_color(&mut image, pixel_color, samples_per_pixel);
         }
     }
-    image
-}
 
-/// Returns the background color
-fn ray_color(ray: &Ray, world: &HittableList, depth: i32) -> Color {
-    if depth <= 0 {
-        return color!(0.0, 0.0, 0.0);
+    for Splat { s, t, color } in splats {
+        let x = ((s * image_width as f64) as usize).min(image_width - 1);
+        let y = ((t * image_height as f64) as usize).min(image_height - 1);
+        pixels[y * image_width + x] += color;
     }
 
-    let desired_hit_distance = 0.001..f64::INFINITY;
+    // Divide the colors by the number of samples
+    for pixel in pixels.iter_mut() {
+        *pixel /= samples_per_pixel as f64;
+    }
+    camera.expose(&mut pixels);
+    pixels
+}
+
+/// Return a PPM image with the given pixels, which are stored row by row
+/// starting with the bottom one
+fn write_image(pixels: &[Color], image_width: usize) -> String {
+    let image_height = pixels.len() / image_width;
 
-    if let Some(hit) = world.hit(ray, &desired_hit_distance) {
-        if let Some((scattered_ray, attenuation)) = hit.material.scatter(ray, &hit) {
-            return attenuation * ray_color(&scattered_ray, world, depth - 1);
+    let mut image = String::new();
+    write!(&mut image, "P3\n{} {}\n255\n", image_width, image_height).unwrap();
+    for row in pixels.chunks(image_width).rev() {
+        for &pixel_color in row {
+            let [red, green, blue] = to_rgb(pixel_color);
+            writeln!(image, "{red} {green} {blue}").unwrap();
         }
-        return color!(0.0, 0.0, 0.0);
     }
+    image
+}
 
-    // We generate a white-blue gradient based on the 'y' coordinate.
-    // The higher the 'y', the 'bluer' the pixel.
-    // The lower the 'y', the whiter the pixel.
-    // Since Rays use unit vectors for directions, it will be blue
-    // the most on the middle-top of the image
-    let t = 0.5 * (ray.direction.y + 1.0);
-    let white = color!(1.0, 1.0, 1.0);
-    let blue = color!(0.5, 0.7, 1.0);
-
-    // This is called a 'linear interpolation'
-    (1.0 - t) * white + t * blue
+/// Write a PNG image with the given pixels, which are stored row by row
+/// starting with the bottom one, to `path`
+fn write_png(pixels: &[Color], image_width: usize, path: &Path) -> io::Result<()> {
+    let image_height = pixels.len() / image_width;
+    let data: Vec<u8> = pixels
+        .chunks(image_width)
+        .rev()
+        .flatten()
+        .flat_map(|&pixel_color| to_rgb(pixel_color))
+        .collect();
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, image_width as u32, image_height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&data)?;
+    Ok(())
 }
 
-fn write_color(image: &mut String, color: Color, samples_per_pixel: u32) {
+/// Return the gamma-corrected 8-bit values of the channels of `color`
+fn to_rgb(color: Color) -> [u8; 3] {
     let Color {
         mut red,
         mut green,
         mut blue,
     } = color;
 
-    // Divide color by number of samples
-    let scale = 1.0 / samples_per_pixel as f64;
-    let scale_color = |c: f64| (c * scale).sqrt();
-
-    red = scale_color(red);
-    green = scale_color(green);
-    blue = scale_color(blue);
+    // Gamma-correct for gamma=2.0
+    red = red.sqrt();
+    green = green.sqrt();
+    blue = blue.sqrt();
 
     let translate_color = |c| 256.0 * clamp(c, 0.0..=0.999);
 
-    let red = translate_color(red) as i32;
-    let green = translate_color(green) as i32;
-    let blue = translate_color(blue) as i32;
-
-    writeln!(image, "{red} {green} {blue}").unwrap();
+    [
+        translate_color(red) as u8,
+        translate_color(green) as u8,
+        translate_color(blue) as u8,
+    ]
 }
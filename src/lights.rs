@@ -0,0 +1,55 @@
+use crate::{
+    hittable::{HitRecord, Hittable},
+    rtweekend::random_num,
+};
+
+/// Every structure of a scene that emits light, so that points on their
+/// surfaces can be sampled
+pub struct Lights<'a> {
+    lights: Vec<&'a dyn Hittable>,
+    /// Sum of the areas of every light up to and including the one at the same index
+    cumulative_areas: Vec<f64>,
+}
+
+impl<'a> Lights<'a> {
+    /// Find every structure in `world` that emits light
+    pub fn new(world: &'a dyn Hittable) -> Lights<'a> {
+        let mut lights = vec![];
+        world.collect_lights(&mut lights);
+
+        let cumulative_areas = lights
+            .iter()
+            .scan(0.0, |total_area, light| {
+                *total_area += light.area();
+                Some(*total_area)
+            })
+            .collect();
+
+        Lights {
+            lights,
+            cumulative_areas,
+        }
+    }
+
+    /// Return the sum of the areas of every light
+    pub fn total_area(&self) -> f64 {
+        self.cumulative_areas.last().copied().unwrap_or(0.0)
+    }
+
+    /// Return a random point on the surface of one of the lights. Every
+    /// point of every light is equally likely to be chosen, so the probability
+    /// density of the point (per unit area) is `pdf_area()`.
+    pub fn sample(&self) -> Option<HitRecord> {
+        let chosen_area = random_num() * self.total_area();
+        let idx = self
+            .cumulative_areas
+            .iter()
+            .position(|&area| area > chosen_area)?;
+        self.lights[idx].sample_surface()
+    }
+
+    /// Probability density (per unit area) of the points returned by `sample()`
+    pub fn pdf_area(&self) -> f64 {
+        1.0 / self.total_area()
+    }
+}
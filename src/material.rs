@@ -1,10 +1,16 @@
 use crate::{
     color,
     color::Color,
+    complex::Complex,
     hittable::HitRecord,
+    microfacet::{fresnel_conductor, fresnel_dielectric, TrowbridgeReitz},
+    onb::Onb,
     ray::Ray,
-    rtweekend::{clamp, random_num},
-    Vec3,
+    rtweekend::{clamp, random_num, PI},
+    spectrum::Dispersion,
+    subsurface::Medium,
+    thin_film::ThinFilm,
+    vec3, Vec3,
 };
 
 pub trait Material {
@@ -12,6 +18,51 @@ pub trait Material {
     /// - Return `None` if the given ray is absorbed
     /// - Return a scattered ray and its attenuation as a `Color` otherwise
     fn scatter(&self, ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)>;
+
+    /// Return the light emitted by this material at the hit point, in the
+    /// direction of the ray that hit it
+    fn emitted(&self, _hit: &HitRecord) -> Color {
+        color!(0.0, 0.0, 0.0)
+    }
+
+    /// Return true if this material emits light
+    fn emits_light(&self) -> bool {
+        false
+    }
+
+    /// Return true if the direction of the scattered rays depends on the
+    /// wavelength of the light, which happens in dispersive materials
+    fn is_dispersive(&self) -> bool {
+        false
+    }
+
+    /// Return the medium that fills the inside of objects made of this
+    /// material, if light can travel through it and interact with it
+    fn medium(&self) -> Option<Medium> {
+        None
+    }
+
+    /// Return how opaque the surface is at the hit point, from 0.0 for fully
+    /// transparent to 1.0 for fully opaque. Rays go through the surface as if
+    /// it wasn't there with a probability of one minus the opacity.
+    fn opacity(&self, _hit: &HitRecord) -> f64 {
+        1.0
+    }
+
+    /// Return the value of the BSDF for light that arrives from direction
+    /// `wi` and leaves towards direction `wo`, both being unit vectors that
+    /// point away from the hit point. Return `None` if the material only
+    /// scatters light in specific directions (like a mirror) and so it can't
+    /// be evaluated for arbitrary ones.
+    fn eval(&self, _wo: Vec3, _wi: Vec3, _hit: &HitRecord) -> Option<Color> {
+        None
+    }
+
+    /// Return the probability density (per unit solid angle) with which
+    /// `scatter` chooses direction `wi` for a ray that leaves towards `wo`
+    fn pdf(&self, _wo: Vec3, _wi: Vec3, _hit: &HitRecord) -> f64 {
+        0.0
+    }
 }
 
 /// Material that always scatters rays and attenuates its reflectance by its `albedo` color.
@@ -27,22 +78,39 @@ impl Lambertian {
 
 impl Material for Lambertian {
     fn scatter(&self, _ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
-        let mut scatter_direction = hit.normal + Vec3::random_unit_vec();
+        let mut scatter_direction = hit.shading_normal + Vec3::random_unit_vec();
 
         // To avoid a zero scatter direction vector, since it could cause
         // Infinity and NaN related problems
         if scatter_direction.is_near_zero() {
-            scatter_direction = hit.normal;
+            scatter_direction = hit.shading_normal;
+        }
+        if !hit.same_side(scatter_direction) {
+            return None;
         }
 
         let scattered_ray = Ray::new(hit.point, scatter_direction);
-        Some((scattered_ray, self.albedo.clone()))
+        Some((scattered_ray, self.albedo))
+    }
+
+    fn eval(&self, _wo: Vec3, wi: Vec3, hit: &HitRecord) -> Option<Color> {
+        if wi.dot(hit.shading_normal) > 0.0 && hit.same_side(wi) {
+            Some(self.albedo / PI)
+        } else {
+            Some(color!(0.0, 0.0, 0.0))
+        }
+    }
+
+    fn pdf(&self, _wo: Vec3, wi: Vec3, hit: &HitRecord) -> f64 {
+        // `normal + random_unit_vec()` is distributed like cos(θ) / π
+        wi.dot(hit.shading_normal).max(0.0) / PI
     }
 }
 
 pub struct Metal {
     albedo: Color,
     fuzzyness: f64,
+    thin_film: Option<ThinFilm>,
 }
 
 impl Metal {
@@ -50,33 +118,275 @@ impl Metal {
         Metal {
             albedo,
             fuzzyness: clamp(fuzzyness, 0.0..=1.0),
+            thin_film: None,
         }
     }
+
+    /// Cover the metal with a thin film with a given thickness in nanometers
+    /// and refraction index, like an oil slick
+    pub fn with_thin_film(mut self, thickness: f64, refraction_idx: f64) -> Metal {
+        self.thin_film = Some(ThinFilm::new(thickness, refraction_idx));
+        self
+    }
+
+    /// Return a complex refraction index for each color channel whose
+    /// reflectance at normal incidence is the albedo, as described by
+    /// Gulbrandsen in "Artist Friendly Metallic Fresnel" (2014). The albedo
+    /// is also used as the color at grazing angles.
+    fn refraction_idx(&self) -> [Complex; 3] {
+        [self.albedo.red, self.albedo.green, self.albedo.blue].map(|reflectance| {
+            let (r, edge_tint) = (clamp(reflectance, 0.0..=0.99), reflectance);
+            let eta = edge_tint * (1.0 - r) / (1.0 + r)
+                + (1.0 - edge_tint) * (1.0 + r.sqrt()) / (1.0 - r.sqrt());
+            let k2 = (r * (eta + 1.0).powi(2) - (eta - 1.0).powi(2)) / (1.0 - r);
+            Complex::new(eta, k2.max(0.0).sqrt())
+        })
+    }
 }
 
 impl Material for Metal {
     fn scatter(&self, ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
-        let reflection_vec = ray_in.direction.unit_vec().reflect(hit.normal);
+        let reflection_vec = ray_in.direction.unit_vec().reflect(hit.shading_normal);
         let scattered_ray = Ray::new(
             hit.point,
             reflection_vec + self.fuzzyness * Vec3::random_unit_vec(),
         );
 
-        if scattered_ray.direction.dot(hit.normal) > 0.0 {
-            Some((scattered_ray, self.albedo.clone()))
+        let attenuation = match self.thin_film {
+            Some(film) => {
+                let cos_theta = hit.shading_normal.dot(-ray_in.direction);
+                film.reflectance(cos_theta, 1.0, self.refraction_idx())
+            }
+            None => self.albedo,
+        };
+
+        let direction = scattered_ray.direction;
+        if direction.dot(hit.normal) > 0.0 && hit.same_side(direction) {
+            Some((scattered_ray, attenuation))
         } else {
             None
         }
     }
 }
 
+/// Metal with a rough surface made of tiny mirrors (microfacets), whose
+/// normals follow a Trowbridge-Reitz (GGX) distribution. How much light each
+/// microfacet reflects is given by the Fresnel equations for its complex index
+/// of refraction `eta + i*k`, for each color channel.
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    distribution: TrowbridgeReitz,
+    thin_film: Option<ThinFilm>,
+}
+
+impl Conductor {
+    /// Create a conductor with a complex index of refraction `eta + i*k` and
+    /// a roughness in the range [0.0, 1.0]
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Conductor {
+        Conductor {
+            eta,
+            k,
+            distribution: TrowbridgeReitz::new(clamp(roughness, 0.0..=1.0)),
+            thin_film: None,
+        }
+    }
+
+    /// Cover the conductor with a thin film with a given thickness in
+    /// nanometers and refraction index, like the oxide layer of heated steel
+    pub fn with_thin_film(mut self, thickness: f64, refraction_idx: f64) -> Conductor {
+        self.thin_film = Some(ThinFilm::new(thickness, refraction_idx));
+        self
+    }
+
+    pub fn gold(roughness: f64) -> Conductor {
+        Conductor::new(
+            color!(0.18299, 0.42108, 1.37340),
+            color!(3.42420, 2.34590, 1.77040),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Conductor {
+        Conductor::new(
+            color!(0.27105, 0.67693, 1.31640),
+            color!(3.60920, 2.62480, 2.29210),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Conductor {
+        Conductor::new(
+            color!(1.65746, 0.88069, 0.52120),
+            color!(9.22387, 6.26952, 4.83700),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Conductor {
+        Conductor::new(
+            color!(0.15943, 0.14512, 0.13547),
+            color!(3.92910, 3.19000, 2.38080),
+            roughness,
+        )
+    }
+
+    /// Fresnel reflectance of each color channel
+    fn fresnel(&self, cos_theta: f64) -> Color {
+        if let Some(film) = self.thin_film {
+            let substrate_idx = [
+                Complex::new(self.eta.red, self.k.red),
+                Complex::new(self.eta.green, self.k.green),
+                Complex::new(self.eta.blue, self.k.blue),
+            ];
+            return film.reflectance(cos_theta, 1.0, substrate_idx);
+        }
+        color!(
+            fresnel_conductor(cos_theta, self.eta.red, self.k.red),
+            fresnel_conductor(cos_theta, self.eta.green, self.k.green),
+            fresnel_conductor(cos_theta, self.eta.blue, self.k.blue)
+        )
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
+        let basis = Onb::from_w(hit.shading_normal);
+        let wo = basis.world_to_local(-ray_in.direction);
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        if self.distribution.is_smooth() {
+            let wi = basis.local_to_world(vec3!(-wo.x, -wo.y, wo.z));
+            if !hit.same_side(wi) {
+                return None;
+            }
+            return Some((Ray::new(hit.point, wi), self.fresnel(wo.z)));
+        }
+
+        // Reflect on a microfacet visible from `wo`. Since the microfacet
+        // is chosen proportionally to its visible area, the BSDF divided by
+        // the pdf is just the Fresnel term times the fraction of the
+        // reflected light that isn't blocked by other microfacets.
+        let wm = self.distribution.sample_visible_normal(wo);
+        let wi = (-wo).reflect(wm);
+        if wi.z <= 0.0 {
+            return None;
+        }
+        let attenuation =
+            self.fresnel(wo.dot(wm)) * (self.distribution.g(wo, wi) / self.distribution.g1(wo));
+        let direction = basis.local_to_world(wi);
+        if !hit.same_side(direction) {
+            return None;
+        }
+        Some((Ray::new(hit.point, direction), attenuation))
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, hit: &HitRecord) -> Option<Color> {
+        if self.distribution.is_smooth() {
+            return None;
+        }
+        if !hit.same_side(wi) {
+            return Some(color!(0.0, 0.0, 0.0));
+        }
+        let basis = Onb::from_w(hit.shading_normal);
+        let (wo, wi) = (basis.world_to_local(wo), basis.world_to_local(wi));
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Some(color!(0.0, 0.0, 0.0));
+        }
+        let wm = (wo + wi).unit_vec();
+        let d = self.distribution.d(wm);
+        let g = self.distribution.g(wo, wi);
+        Some(self.fresnel(wo.dot(wm)) * (d * g / (4.0 * wo.z * wi.z)))
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, hit: &HitRecord) -> f64 {
+        let basis = Onb::from_w(hit.shading_normal);
+        let (wo, wi) = (basis.world_to_local(wo), basis.world_to_local(wi));
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = (wo + wi).unit_vec();
+        self.distribution.pdf_visible_normal(wo, wm) / (4.0 * wo.dot(wm))
+    }
+}
+
+/// Return the absorption coefficient of a medium in which light keeps only
+/// `color` of its intensity after traveling `distance`
+fn absorption_coefficient(color: Color, distance: f64) -> Color {
+    let coefficient = |c: f64| -c.max(1e-6).ln() / distance;
+    color!(
+        coefficient(color.red),
+        coefficient(color.green),
+        coefficient(color.blue)
+    )
+}
+
+/// Return the fraction of light that isn't absorbed (following the
+/// Beer-Lambert law) by a medium with the given absorption coefficient, when
+/// `hit` is on the inner side of the medium's surface. The distance traveled
+/// inside the medium is then the distance of the hit.
+fn transmittance(absorption: Color, hit: &HitRecord) -> Color {
+    if hit.front_face {
+        return color!(1.0, 1.0, 1.0);
+    }
+    let transmittance = |a: f64| (-a * hit.dist).exp();
+    color!(
+        transmittance(absorption.red),
+        transmittance(absorption.green),
+        transmittance(absorption.blue)
+    )
+}
+
 pub struct Dielectric {
     refraction_idx: f64,
+    dispersion: Option<Dispersion>,
+    absorption: Color,
+    thin_film: Option<ThinFilm>,
 }
 
 impl Dielectric {
     pub fn new(refraction_idx: f64) -> Dielectric {
-        Dielectric { refraction_idx }
+        Dielectric {
+            refraction_idx,
+            dispersion: None,
+            absorption: color!(0.0, 0.0, 0.0),
+            thin_film: None,
+        }
+    }
+
+    /// Cover the outside of the dielectric with a thin film with a given
+    /// thickness in nanometers and refraction index, like a soap bubble
+    pub fn with_thin_film(mut self, thickness: f64, refraction_idx: f64) -> Dielectric {
+        self.thin_film = Some(ThinFilm::new(thickness, refraction_idx));
+        self
+    }
+
+    /// Make the refraction index depend on the wavelength of light, which
+    /// splits white light into its colors when rendering in spectral mode.
+    /// Otherwise, the refraction index at `Dispersion::REFERENCE_WAVELENGTH`
+    /// is used.
+    pub fn with_dispersion(mut self, dispersion: Dispersion) -> Dielectric {
+        self.refraction_idx = dispersion.refraction_idx(Dispersion::REFERENCE_WAVELENGTH);
+        self.dispersion = Some(dispersion);
+        self
+    }
+
+    /// Return the refraction index for a ray carrying light of the given
+    /// wavelength
+    fn refraction_idx(&self, wavelength: Option<f64>) -> f64 {
+        match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(wavelength)) => dispersion.refraction_idx(wavelength),
+            _ => self.refraction_idx,
+        }
+    }
+
+    /// Tint the light that travels inside this material, so that only `color`
+    /// of it remains after traveling `distance`
+    pub fn with_tint(mut self, color: Color, distance: f64) -> Dielectric {
+        self.absorption = absorption_coefficient(color, distance);
+        self
     }
 
     /// Calculate the reflectance given the cosine of the angle and a
@@ -91,33 +401,294 @@ impl Dielectric {
 
 impl Material for Dielectric {
     fn scatter(&self, ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
-        let attenuation = color!(1.0, 1.0, 1.0);
+        let attenuation = transmittance(self.absorption, hit);
 
+        let refraction_idx = self.refraction_idx(ray_in.wavelength);
         let refraction_ratio = if hit.front_face {
-            1.0 / self.refraction_idx
+            1.0 / refraction_idx
         } else {
-            self.refraction_idx
+            refraction_idx
         };
 
         // I had forgotten the '-' sign here. No work since October just for this lmao
-        let cos_theta = hit.normal.dot(-ray_in.direction).min(1.0);
+        let cos_theta = hit.shading_normal.dot(-ray_in.direction).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         // Must reflect, ergo, cannot refract
         let must_reflect = refraction_ratio * sin_theta > 1.0;
 
+        if let (Some(film), false) = (self.thin_film, must_reflect) {
+            // The film reflects each color differently, so choose whether to
+            // reflect by the average reflectance and weight each color by
+            // how likely it was to be chosen
+            let (outside_idx, inside_idx) = if hit.front_face {
+                (1.0, refraction_idx)
+            } else {
+                (refraction_idx, 1.0)
+            };
+            let reflectance = film.reflectance(cos_theta, outside_idx, [inside_idx.into(); 3]);
+            let probability = (reflectance.red + reflectance.green + reflectance.blue) / 3.0;
+            let (new_direction, weight) = if random_num() < probability {
+                let direction = ray_in.direction.reflect(hit.shading_normal);
+                (direction, reflectance / probability)
+            } else {
+                let direction = ray_in
+                    .direction
+                    .refract(hit.shading_normal, refraction_ratio);
+                let transmitted = color!(1.0, 1.0, 1.0) - reflectance;
+                (direction, transmitted / (1.0 - probability))
+            };
+            if !hit.same_side(new_direction) {
+                return None;
+            }
+            return Some((Ray::new(hit.point, new_direction), attenuation * weight));
+        }
+
         let reflectance_too_high =
             Dielectric::reflectance(cos_theta, refraction_ratio) > random_num();
         // let reflectance_too_high = false;
 
         let new_direction = if must_reflect || reflectance_too_high {
-            ray_in.direction.reflect(hit.normal)
+            ray_in.direction.reflect(hit.shading_normal)
         } else {
-            ray_in.direction.refract(hit.normal, refraction_ratio)
+            ray_in
+                .direction
+                .refract(hit.shading_normal, refraction_ratio)
         };
+        if !hit.same_side(new_direction) {
+            return None;
+        }
 
         let refracted_ray = Ray::new(hit.point, new_direction);
 
         Some((refracted_ray, attenuation))
     }
+
+    fn is_dispersive(&self) -> bool {
+        self.dispersion.is_some()
+    }
+}
+
+/// Dielectric with a rough surface (like frosted glass) made of microfacets
+/// whose normals follow a Trowbridge-Reitz (GGX) distribution. Light is
+/// reflected and refracted by the microfacets as described by Walter et al.
+/// in "Microfacet Models for Refraction through Rough Surfaces" (2007).
+pub struct RoughDielectric {
+    refraction_idx: f64,
+    distribution: TrowbridgeReitz,
+    absorption: Color,
+}
+
+impl RoughDielectric {
+    /// Create a rough dielectric with a given refraction index and a roughness
+    /// in the range [0.0, 1.0]
+    pub fn new(refraction_idx: f64, roughness: f64) -> RoughDielectric {
+        RoughDielectric {
+            refraction_idx,
+            distribution: TrowbridgeReitz::new(clamp(roughness, 0.0..=1.0)),
+            absorption: color!(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Tint the light that travels inside this material, so that only `color`
+    /// of it remains after traveling `distance`
+    pub fn with_tint(mut self, color: Color, distance: f64) -> RoughDielectric {
+        self.absorption = absorption_coefficient(color, distance);
+        self
+    }
+
+    /// Return the local basis of the surface, whose normal always points
+    /// outside of the material
+    pub(crate) fn basis(hit: &HitRecord) -> Onb {
+        if hit.front_face {
+            Onb::from_w(hit.shading_normal)
+        } else {
+            Onb::from_w(-hit.shading_normal)
+        }
+    }
+
+    /// Return the microfacet normal that reflects or refracts `wi` into `wo`,
+    /// if there is any, pointing to the outside of the material. The
+    /// directions are given in local coordinates.
+    fn half_vector(&self, wo: Vec3, wi: Vec3) -> Option<Vec3> {
+        let reflect = wo.z * wi.z > 0.0;
+        let eta = match (reflect, wo.z > 0.0) {
+            (true, _) => 1.0,
+            (false, true) => self.refraction_idx,
+            (false, false) => 1.0 / self.refraction_idx,
+        };
+        let wm = wi * eta + wo;
+        if wo.z == 0.0 || wi.z == 0.0 || wm.is_near_zero() {
+            return None;
+        }
+        let wm = wm.unit_vec();
+        let wm = if wm.z < 0.0 { -wm } else { wm };
+
+        // Discard microfacets that face away from any of the directions
+        if wm.dot(wi) * wi.z < 0.0 || wm.dot(wo) * wo.z < 0.0 {
+            return None;
+        }
+        Some(wm)
+    }
+
+    /// Return a direction `wi` for light leaving towards `wo`, sampled
+    /// proportionally to the visible microfacets and their Fresnel reflectance.
+    /// The directions are given in local coordinates.
+    pub(crate) fn local_sample(&self, wo: Vec3) -> Option<Vec3> {
+        // Choose a visible microfacet, and then reflect or refract on it
+        // depending on its Fresnel reflectance
+        let wm = self.distribution.sample_visible_normal(wo);
+        let reflectance = fresnel_dielectric(wo.dot(wm), self.refraction_idx);
+        if random_num() < reflectance {
+            let wi = (-wo).reflect(wm);
+            Some(wi).filter(|wi| wi.z * wo.z > 0.0)
+        } else {
+            refract_local(wo, wm, self.refraction_idx).filter(|wi| wi.z * wo.z < 0.0)
+        }
+    }
+
+    /// BSDF for directions given in local coordinates
+    pub(crate) fn local_eval(&self, wo: Vec3, wi: Vec3) -> f64 {
+        let wm = match self.half_vector(wo, wi) {
+            Some(wm) => wm,
+            None => return 0.0,
+        };
+        let reflectance = fresnel_dielectric(wo.dot(wm), self.refraction_idx);
+        let d = self.distribution.d(wm);
+        let g = self.distribution.g(wo, wi);
+
+        if wo.z * wi.z > 0.0 {
+            reflectance * d * g / (4.0 * wo.z * wi.z).abs()
+        } else {
+            let eta = if wo.z > 0.0 {
+                self.refraction_idx
+            } else {
+                1.0 / self.refraction_idx
+            };
+            let denom = (wi.dot(wm) + wo.dot(wm) / eta).powi(2) * wi.z * wo.z;
+            (1.0 - reflectance) * d * g * (wi.dot(wm) * wo.dot(wm) / denom).abs()
+        }
+    }
+
+    /// Probability density of `scatter` choosing `wi`, for directions given in
+    /// local coordinates
+    pub(crate) fn local_pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        let wm = match self.half_vector(wo, wi) {
+            Some(wm) => wm,
+            None => return 0.0,
+        };
+        let reflectance = fresnel_dielectric(wo.dot(wm), self.refraction_idx);
+        let pdf_wm = self.distribution.pdf_visible_normal(wo, wm);
+
+        if wo.z * wi.z > 0.0 {
+            pdf_wm / (4.0 * wo.dot(wm).abs()) * reflectance
+        } else {
+            let eta = if wo.z > 0.0 {
+                self.refraction_idx
+            } else {
+                1.0 / self.refraction_idx
+            };
+            let denom = (wi.dot(wm) + wo.dot(wm) / eta).powi(2);
+            pdf_wm * (wi.dot(wm).abs() / denom) * (1.0 - reflectance)
+        }
+    }
+}
+
+/// Return the direction in which light leaving in direction `wo` arrived
+/// after being refracted by a surface with normal `normal` and whose inside
+/// has a refraction index `refraction_idx`, or `None` if there is total
+/// internal reflection. `wo` may be on either side of `normal`.
+fn refract_local(wo: Vec3, normal: Vec3, refraction_idx: f64) -> Option<Vec3> {
+    // Light arrives from the other side, so it goes inside when `wo` is
+    // outside
+    let (normal, refraction_ratio) = if wo.dot(normal) > 0.0 {
+        (normal, 1.0 / refraction_idx)
+    } else {
+        (-normal, refraction_idx)
+    };
+    let cos_theta = wo.dot(normal).min(1.0);
+    let sin_theta_squared = 1.0 - cos_theta * cos_theta;
+    if refraction_ratio * refraction_ratio * sin_theta_squared > 1.0 {
+        // Total internal reflection
+        return None;
+    }
+    Some((-wo).refract(normal, refraction_ratio))
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
+        let basis = RoughDielectric::basis(hit);
+        let wo = basis.world_to_local(-ray_in.direction);
+        let attenuation = transmittance(self.absorption, hit);
+
+        if self.distribution.is_smooth() {
+            let normal = vec3!(0.0, 0.0, 1.0);
+            let reflectance = fresnel_dielectric(wo.z, self.refraction_idx);
+            let wi = match refract_local(wo, normal, self.refraction_idx) {
+                Some(wi) if random_num() >= reflectance => wi,
+                _ => (-wo).reflect(normal),
+            };
+            let direction = basis.local_to_world(wi);
+            if !hit.same_side(direction) {
+                return None;
+            }
+            return Some((Ray::new(hit.point, direction), attenuation));
+        }
+
+        let wi = self.local_sample(wo)?;
+        let pdf = self.local_pdf(wo, wi);
+        let direction = basis.local_to_world(wi);
+        if pdf == 0.0 || !hit.same_side(direction) {
+            return None;
+        }
+        let weight = self.local_eval(wo, wi) * wi.z.abs() / pdf;
+        Some((Ray::new(hit.point, direction), weight * attenuation))
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, hit: &HitRecord) -> Option<Color> {
+        if self.distribution.is_smooth() {
+            return None;
+        }
+        if !hit.same_side(wi) {
+            return Some(color!(0.0, 0.0, 0.0));
+        }
+        let basis = RoughDielectric::basis(hit);
+        let f = self.local_eval(basis.world_to_local(wo), basis.world_to_local(wi));
+        Some(f * transmittance(self.absorption, hit))
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, hit: &HitRecord) -> f64 {
+        let basis = RoughDielectric::basis(hit);
+        self.local_pdf(basis.world_to_local(wo), basis.world_to_local(wi))
+    }
+}
+
+/// Material that emits light of a given color from its front face and
+/// doesn't scatter any rays
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray_in: &Ray, _hit: &HitRecord) -> Option<(Ray, Color)> {
+        None
+    }
+
+    fn emitted(&self, hit: &HitRecord) -> Color {
+        if hit.front_face {
+            self.emit
+        } else {
+            color!(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn emits_light(&self) -> bool {
+        true
+    }
 }
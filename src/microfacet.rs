@@ -0,0 +1,198 @@
+use crate::{
+    rtweekend::{random_num, PI},
+    vec3,
+    vector3::Vec3,
+};
+
+/// Trowbridge-Reitz (also known as GGX) distribution of microfacet normals.
+///
+/// Every direction is given in the local coordinates of the surface, where
+/// the Z axis is the surface's normal.
+#[derive(Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha: f64,
+}
+
+impl TrowbridgeReitz {
+    /// Create a distribution for a given perceptual roughness in the range
+    /// [0.0, 1.0]. Its alpha parameter is the squared roughness.
+    pub fn new(roughness: f64) -> TrowbridgeReitz {
+        TrowbridgeReitz {
+            alpha: roughness * roughness,
+        }
+    }
+
+    /// Return true if the surface is so smooth that it should be treated as
+    /// a perfect mirror, because the distribution is too close to a delta
+    /// function to be evaluated
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < 1e-3
+    }
+
+    /// Return the differential area of microfacets with normal `wm`
+    pub fn d(&self, wm: Vec3) -> f64 {
+        let cos2_theta = wm.z * wm.z;
+        if cos2_theta == 0.0 {
+            return 0.0;
+        }
+        let tan2_theta = (1.0 - cos2_theta) / cos2_theta;
+        let alpha2 = self.alpha * self.alpha;
+        let e = 1.0 + tan2_theta / alpha2;
+        1.0 / (PI * alpha2 * cos2_theta * cos2_theta * e * e)
+    }
+
+    /// Smith's auxiliary function, which measures the invisible area of the
+    /// microfacets per visible area in direction `w`
+    fn lambda(&self, w: Vec3) -> f64 {
+        let cos2_theta = w.z * w.z;
+        if cos2_theta == 0.0 {
+            return f64::INFINITY;
+        }
+        let tan2_theta = (1.0 - cos2_theta) / cos2_theta;
+        ((1.0 + self.alpha * self.alpha * tan2_theta).sqrt() - 1.0) / 2.0
+    }
+
+    /// Return the fraction of microfacets that are visible from direction `w`
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Return the fraction of microfacets that are visible from both `wo` and `wi`
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Return the probability density of `sample_visible_normal` returning
+    /// `wm` when seen from direction `w`
+    pub fn pdf_visible_normal(&self, w: Vec3, wm: Vec3) -> f64 {
+        if w.z == 0.0 {
+            return 0.0;
+        }
+        self.g1(w) / w.z.abs() * self.d(wm) * w.dot(wm).abs()
+    }
+
+    /// Return a random microfacet normal among the ones visible from direction
+    /// `w`, with probability proportional to their visible area (Heitz 2018)
+    pub fn sample_visible_normal(&self, w: Vec3) -> Vec3 {
+        // Transform `w` to the configuration where the distribution is a
+        // hemisphere, and make sure it is in the upper one
+        let mut wh = vec3!(self.alpha * w.x, self.alpha * w.y, w.z).unit_vec();
+        if wh.z < 0.0 {
+            wh = -wh;
+        }
+
+        // Orthonormal basis around `wh`
+        let t1 = if wh.z < 0.99999 {
+            vec3!(0.0, 0.0, 1.0).cross(wh).unit_vec()
+        } else {
+            vec3!(1.0, 0.0, 0.0)
+        };
+        let t2 = wh.cross(t1);
+
+        // Uniformly distributed point of a disc, warped to take into account
+        // the projection of the hemisphere seen from `wh`
+        let r = random_num().sqrt();
+        let phi = 2.0 * PI * random_num();
+        let px = r * phi.cos();
+        let mut py = r * phi.sin();
+        let h = (1.0 - px * px).sqrt();
+        let s = (1.0 + wh.z) / 2.0;
+        py = (1.0 - s) * h + s * py;
+
+        // Project the point to the hemisphere and transform the normal back
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+        let nh = px * t1 + py * t2 + pz * wh;
+        vec3!(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(1e-6)).unit_vec()
+    }
+}
+
+/// Fresnel reflectance of a conductor with complex index of refraction
+/// `eta + i*k`, for light whose direction forms an angle with the normal
+/// whose cosine is `cos_theta`
+pub fn fresnel_conductor(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos2.sqrt() * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    (rs + rp) / 2.0
+}
+
+/// Fresnel reflectance of a dielectric for light whose direction forms an
+/// angle with the normal whose cosine is `cos_theta_i`. `eta` is the ratio
+/// between the refraction index of the side the normal points away from and
+/// the one of the side it points to. `cos_theta_i` is negative when the light
+/// comes from the former side.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let (cos_theta_i, eta) = if cos_theta_i < 0.0 {
+        (-cos_theta_i, 1.0 / eta)
+    } else {
+        (cos_theta_i.min(1.0), eta)
+    };
+
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        // Total internal reflection
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+/// Generalized Trowbridge-Reitz distribution with exponent 1, used by the
+/// clear coat of `Principled`. It has a longer tail than `TrowbridgeReitz`.
+#[derive(Clone, Copy)]
+pub struct Gtr1 {
+    alpha: f64,
+}
+
+impl Gtr1 {
+    pub fn new(alpha: f64) -> Gtr1 {
+        Gtr1 {
+            alpha: alpha.max(1e-3),
+        }
+    }
+
+    /// Return the differential area of microfacets with normal `wm`
+    pub fn d(&self, wm: Vec3) -> f64 {
+        let alpha2 = self.alpha * self.alpha;
+        let t = 1.0 + (alpha2 - 1.0) * wm.z * wm.z;
+        (alpha2 - 1.0) / (PI * alpha2.ln() * t)
+    }
+
+    /// Return a random microfacet normal, with probability `d(wm) * cos(θm)`
+    pub fn sample_normal(&self) -> Vec3 {
+        let alpha2 = self.alpha * self.alpha;
+        let cos_theta = ((1.0 - alpha2.powf(1.0 - random_num())) / (1.0 - alpha2))
+            .max(0.0)
+            .sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_num();
+        vec3!(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+    }
+
+    /// Return the probability density of `sample_normal` returning `wm`
+    pub fn pdf_normal(&self, wm: Vec3) -> f64 {
+        self.d(wm) * wm.z.abs()
+    }
+}
+
+/// Schlick's approximation of the Fresnel reflectance, given the reflectance
+/// at normal incidence `r0`
+pub fn fresnel_schlick(r0: f64, cos_theta: f64) -> f64 {
+    r0 + (1.0 - r0) * (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
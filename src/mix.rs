@@ -0,0 +1,152 @@
+use std::rc::Rc;
+
+use crate::{
+    color::Color, hittable::HitRecord, material::Material, microfacet::fresnel_dielectric,
+    ray::Ray, rtweekend::random_num, subsurface::Medium, texture::Texture, vector3::Vec3,
+};
+
+/// How much of the second material of a `MixMaterial` there is at a point
+pub enum MixWeight {
+    /// The same weight everywhere, in the range [0.0, 1.0]
+    Constant(f64),
+    /// The weight is the luminance of a texture, like a dirt mask
+    Texture(Rc<dyn Texture>),
+    /// The weight is the Fresnel reflectance of a dielectric with the given
+    /// refraction index, so that the second material shows up at grazing
+    /// angles, like on the edges of an object.
+    ///
+    /// The direction of the light is only known when it is scattered, so the
+    /// emitted light and the opacity are blended with the weight at normal
+    /// incidence.
+    Fresnel(f64),
+}
+
+/// Material that is a blend of two other materials. Each time light hits it,
+/// one of them is chosen at random with a probability equal to its weight,
+/// so only the chosen one is sampled.
+pub struct MixMaterial {
+    first: Rc<dyn Material>,
+    second: Rc<dyn Material>,
+    weight: MixWeight,
+}
+
+impl MixMaterial {
+    /// Create a blend of `first` and `second`, where `weight` tells how much
+    /// of `second` there is
+    pub fn new(
+        first: Rc<dyn Material>,
+        second: Rc<dyn Material>,
+        weight: MixWeight,
+    ) -> MixMaterial {
+        MixMaterial {
+            first,
+            second,
+            weight,
+        }
+    }
+
+    /// Return the weight of the second material, for light leaving the hit
+    /// point towards `wo`
+    fn weight(&self, wo: Vec3, hit: &HitRecord) -> f64 {
+        let weight = match &self.weight {
+            MixWeight::Constant(weight) => *weight,
+            MixWeight::Texture(texture) => texture.value(hit.u, hit.v, hit.point).luminance(),
+            MixWeight::Fresnel(refraction_idx) => {
+                fresnel_dielectric(wo.dot(hit.shading_normal).abs(), *refraction_idx)
+            }
+        };
+        weight.clamp(0.0, 1.0)
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(&self, ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
+        // Choosing each material with a probability equal to its weight
+        // cancels out the weight, so the attenuation is the one of the chosen
+        // material
+        if random_num() < self.weight(-ray_in.direction, hit) {
+            self.second.scatter(ray_in, hit)
+        } else {
+            self.first.scatter(ray_in, hit)
+        }
+    }
+
+    fn emitted(&self, hit: &HitRecord) -> Color {
+        // Light sampled on the surface has no direction yet, so Fresnel
+        // weights are taken at normal incidence
+        let weight = self.weight(hit.shading_normal, hit);
+        (1.0 - weight) * self.first.emitted(hit) + weight * self.second.emitted(hit)
+    }
+
+    fn emits_light(&self) -> bool {
+        self.first.emits_light() || self.second.emits_light()
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.first.is_dispersive() || self.second.is_dispersive()
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        // A constant weight of 0.0 or 1.0 always chooses the same material.
+        // Otherwise, the inside of the object is filled with the medium of
+        // either material, since there can only be one.
+        match self.weight {
+            MixWeight::Constant(weight) if weight <= 0.0 => self.first.medium(),
+            MixWeight::Constant(weight) if weight >= 1.0 => self.second.medium(),
+            _ => self.first.medium().or_else(|| self.second.medium()),
+        }
+    }
+
+    fn opacity(&self, hit: &HitRecord) -> f64 {
+        // As for emitted light, Fresnel weights are taken at normal incidence
+        let weight = self.weight(hit.shading_normal, hit);
+        (1.0 - weight) * self.first.opacity(hit) + weight * self.second.opacity(hit)
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, hit: &HitRecord) -> Option<Color> {
+        // The blend can only be evaluated when both materials can, since
+        // otherwise `scatter` could choose directions that `eval` can't
+        // account for
+        let first = self.first.eval(wo, wi, hit)?;
+        let second = self.second.eval(wo, wi, hit)?;
+        let weight = self.weight(wo, hit);
+        Some((1.0 - weight) * first + weight * second)
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, hit: &HitRecord) -> f64 {
+        let weight = self.weight(wo, hit);
+        (1.0 - weight) * self.first.pdf(wo, wi, hit) + weight * self.second.pdf(wo, wi, hit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color, material::Lambertian, subsurface::Subsurface};
+
+    #[test]
+    fn forwards_the_medium_of_the_materials() {
+        let lambertian = || -> Rc<dyn Material> { Rc::new(Lambertian::new(color!(0.5, 0.5, 0.5))) };
+        let subsurface = || -> Rc<dyn Material> {
+            Rc::new(Subsurface::new(
+                color!(0.8, 0.8, 0.8),
+                color!(1.0, 1.0, 1.0),
+                1.3,
+            ))
+        };
+        let mix = |first, second, weight| MixMaterial::new(first, second, weight);
+
+        assert!(mix(lambertian(), subsurface(), MixWeight::Constant(0.5))
+            .medium()
+            .is_some());
+        assert!(mix(subsurface(), lambertian(), MixWeight::Fresnel(1.5))
+            .medium()
+            .is_some());
+        assert!(mix(lambertian(), subsurface(), MixWeight::Constant(0.0))
+            .medium()
+            .is_none());
+        assert!(mix(subsurface(), lambertian(), MixWeight::Constant(1.0))
+            .medium()
+            .is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use std::rc::Rc;
+
+use crate::{
+    color::Color, hittable::HitRecord, material::Material, onb::Onb, ray::Ray, subsurface::Medium,
+    texture::Texture, vec3, vector3::Vec3,
+};
+
+/// Return the shading normal of `hit` pointing outside of the surface
+fn outward_shading_normal(hit: &HitRecord) -> Vec3 {
+    if hit.front_face {
+        hit.shading_normal
+    } else {
+        -hit.shading_normal
+    }
+}
+
+/// Return a copy of `hit` whose shading normal is `normal`, which points
+/// outside of the surface
+fn with_shading_normal(hit: &HitRecord, normal: Vec3) -> HitRecord {
+    let normal = normal.unit_vec();
+    let mut hit = hit.clone();
+    // Keep the shading normal of a hit on the same side as its normal
+    hit.shading_normal = if hit.front_face { normal } else { -normal };
+    hit
+}
+
+/// Material that shades another one with the normals of a normal map, which
+/// adds small details to a surface without changing its geometry.
+///
+/// The normals are given in tangent space, where red, green and blue are the
+/// components along the U direction, the V direction and the normal of the
+/// surface, mapped from [-1.0, 1.0] to [0.0, 1.0]. This is how most tools
+/// store normal maps, which should be loaded with `ImageTexture::load_linear`.
+pub struct NormalMap {
+    texture: Rc<dyn Texture>,
+    material: Rc<dyn Material>,
+}
+
+impl NormalMap {
+    pub fn new(texture: Rc<dyn Texture>, material: Rc<dyn Material>) -> NormalMap {
+        NormalMap { texture, material }
+    }
+
+    /// Return `hit` with the shading normal given by the normal map
+    fn perturb(&self, hit: &HitRecord) -> HitRecord {
+        let normal = outward_shading_normal(hit);
+
+        // Tangent in the U direction, perpendicular to the normal. Surfaces
+        // without derivatives get an arbitrary one.
+        let tangent = hit.dpdu - normal * normal.dot(hit.dpdu);
+        let tangent = if tangent.length_squared() > 1e-16 {
+            tangent.unit_vec()
+        } else {
+            Onb::from_w(normal).u
+        };
+        let bitangent = normal.cross(tangent);
+
+        let color = self.texture.value(hit.u, hit.v, hit.point);
+        let local = vec3!(
+            2.0 * color.red - 1.0,
+            2.0 * color.green - 1.0,
+            2.0 * color.blue - 1.0
+        );
+        let mapped = local.x * tangent + local.y * bitangent + local.z * normal;
+        if mapped.is_near_zero() {
+            return hit.clone();
+        }
+        with_shading_normal(hit, mapped)
+    }
+}
+
+/// Material that shades another one as if its surface were displaced along
+/// its normal by a height map, which makes it look bumpy.
+///
+/// Heights are the luminance of a texture times `scale`, in the same units as
+/// the scene. Only surfaces that provide derivatives with respect to their
+/// surface coordinates (like `Sphere`) can be bump mapped.
+pub struct BumpMap {
+    height: Rc<dyn Texture>,
+    scale: f64,
+    material: Rc<dyn Material>,
+}
+
+impl BumpMap {
+    /// Step in surface coordinates used to find the slope of the height map
+    const DELTA: f64 = 1.0 / 1024.0;
+
+    pub fn new(height: Rc<dyn Texture>, scale: f64, material: Rc<dyn Material>) -> BumpMap {
+        BumpMap {
+            height,
+            scale,
+            material,
+        }
+    }
+
+    /// Return the height of the surface at some surface coordinates
+    fn height(&self, u: f64, v: f64, point: Vec3) -> f64 {
+        self.scale * self.height.value(u, v, point).luminance()
+    }
+
+    /// Return `hit` with the shading normal of the displaced surface
+    fn perturb(&self, hit: &HitRecord) -> HitRecord {
+        let normal = outward_shading_normal(hit);
+        let delta = BumpMap::DELTA;
+
+        // Slope of the height map, found with finite differences. Since
+        // coordinates are clamped to [0.0, 1.0], the step goes backwards near
+        // the end of the range.
+        let du = if hit.u + delta > 1.0 { -delta } else { delta };
+        let dv = if hit.v + delta > 1.0 { -delta } else { delta };
+        let height = self.height(hit.u, hit.v, hit.point);
+        let dhdu = (self.height(hit.u + du, hit.v, hit.point + du * hit.dpdu) - height) / du;
+        let dhdv = (self.height(hit.u, hit.v + dv, hit.point + dv * hit.dpdv) - height) / dv;
+
+        // Derivatives of the displaced surface, whose cross product is its
+        // normal
+        let dpdu = hit.dpdu + dhdu * normal;
+        let dpdv = hit.dpdv + dhdv * normal;
+        let bumped = dpdu.cross(dpdv);
+        if bumped.is_near_zero() {
+            return hit.clone();
+        }
+        let bumped = if bumped.dot(normal) < 0.0 {
+            -bumped
+        } else {
+            bumped
+        };
+        with_shading_normal(hit, bumped)
+    }
+}
+
+/// Implement `Material` for a type with a `perturb` method and a `material`
+/// field, by calling the material with the perturbed hit record
+macro_rules! impl_perturbed_material {
+    ($type:ty) => {
+        impl Material for $type {
+            fn scatter(&self, ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
+                self.material.scatter(ray_in, &self.perturb(hit))
+            }
+
+            fn emitted(&self, hit: &HitRecord) -> Color {
+                self.material.emitted(&self.perturb(hit))
+            }
+
+            fn emits_light(&self) -> bool {
+                self.material.emits_light()
+            }
+
+            fn is_dispersive(&self) -> bool {
+                self.material.is_dispersive()
+            }
+
+            fn medium(&self) -> Option<Medium> {
+                self.material.medium()
+            }
+
+            fn opacity(&self, hit: &HitRecord) -> f64 {
+                self.material.opacity(hit)
+            }
+
+            fn eval(&self, wo: Vec3, wi: Vec3, hit: &HitRecord) -> Option<Color> {
+                self.material.eval(wo, wi, &self.perturb(hit))
+            }
+
+            fn pdf(&self, wo: Vec3, wi: Vec3, hit: &HitRecord) -> f64 {
+                self.material.pdf(wo, wi, &self.perturb(hit))
+            }
+        }
+    };
+}
+
+impl_perturbed_material!(NormalMap);
+impl_perturbed_material!(BumpMap);
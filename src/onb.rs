@@ -0,0 +1,35 @@
+use crate::{vec3, vector3::Vec3};
+
+/// Orthonormal basis: three unit vectors perpendicular to each other
+#[derive(Clone, Copy)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    /// Create an orthonormal basis whose `w` vector has the direction of `normal`
+    pub fn from_w(normal: Vec3) -> Onb {
+        let w = normal.unit_vec();
+        // Any vector that is not parallel to `w` will do
+        let a = if w.x.abs() > 0.9 {
+            vec3!(0.0, 1.0, 0.0)
+        } else {
+            vec3!(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(a).unit_vec();
+        let u = v.cross(w);
+        Onb { u, v, w }
+    }
+
+    /// Transform a vector from this basis' local coordinates to world coordinates
+    pub fn local_to_world(&self, local: Vec3) -> Vec3 {
+        local.x * self.u + local.y * self.v + local.z * self.w
+    }
+
+    /// Transform a vector from world coordinates to this basis' local coordinates
+    pub fn world_to_local(&self, world: Vec3) -> Vec3 {
+        vec3!(world.dot(self.u), world.dot(self.v), world.dot(self.w))
+    }
+}
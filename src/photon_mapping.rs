@@ -0,0 +1,310 @@
+use crate::{
+    camera::Camera,
+    color,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    integrator::{background, Integrator},
+    kd_tree::KdTree,
+    lights::Lights,
+    onb::Onb,
+    ray::Ray,
+    rtweekend::{random_num, PI},
+    vector3::Vec3,
+    write_image,
+};
+
+const HIT_RANGE: std::ops::Range<f64> = 0.001..f64::INFINITY;
+
+/// Light that arrived at a point of a diffuse surface
+struct Photon {
+    /// Unit vector pointing to where the photon came from
+    wi: Vec3,
+    /// Power carried by the photon. Its sum for every photon that was shot
+    /// has to be divided by the number of photons shot.
+    power: Color,
+}
+
+/// Return true if the material of `hit` is not specular, which means that it
+/// can be evaluated for any pair of directions
+fn is_diffuse(hit: &HitRecord, wo: Vec3) -> bool {
+    hit.material.eval(wo, wo, hit).is_some()
+}
+
+/// Shoot `count` photons from random points of the lights of `world`, and
+/// store where they hit a diffuse surface during their first `max_depth`
+/// bounces.
+fn shoot_photons(world: &dyn Hittable, count: usize, max_depth: u32) -> KdTree<Photon> {
+    let lights = Lights::new(world);
+    let mut photons = vec![];
+
+    for _ in 0..count {
+        // Photons that start at a cut out point are lost, but they still
+        // count as shot
+        let light = match lights.sample() {
+            Some(light) => light,
+            None => continue,
+        };
+
+        // Emit the photon in a cosine-weighted direction, so that its power
+        // is `emitted * cos(θ) / (pdf_area * cos(θ) / π)`
+        let direction = Onb::from_w(light.normal).local_to_world(Vec3::random_cosine_direction());
+        let mut power = light.material.emitted(&light) * (PI / lights.pdf_area());
+        let mut ray = Ray::new(light.point, direction);
+
+        for _ in 0..max_depth {
+            let hit = match world.hit(&ray, &HIT_RANGE) {
+                Some(hit) => hit,
+                None => break,
+            };
+
+            let wi = -ray.direction;
+            if is_diffuse(&hit, wi) {
+                photons.push((hit.point, Photon { wi, power }));
+            }
+
+            match hit.material.scatter(&ray, &hit) {
+                Some((scattered_ray, attenuation)) => {
+                    power = power * attenuation;
+                    ray = scattered_ray;
+                }
+                None => break,
+            }
+        }
+    }
+    KdTree::new(photons)
+}
+
+/// First point of a diffuse surface seen by a camera ray, after going
+/// through any number of specular bounces
+struct VisiblePoint {
+    hit: HitRecord,
+    /// Unit vector pointing to the camera (or to the previous bounce)
+    wo: Vec3,
+    /// Attenuation of the specular bounces before the visible point
+    throughput: Color,
+}
+
+impl VisiblePoint {
+    /// Return the sum of the light reflected towards `wo` by the photons at a
+    /// distance of `radius` or less, and how many photons there are
+    fn gather(&self, photons: &KdTree<Photon>, radius: f64) -> (Color, usize) {
+        let mut flux = color!(0.0, 0.0, 0.0);
+        let mut count = 0;
+        photons.for_each_within(self.hit.point, radius, |_, photon| {
+            if let Some(bsdf) = self.hit.material.eval(self.wo, photon.wi, &self.hit) {
+                flux += bsdf * photon.power;
+                count += 1;
+            }
+        });
+        (self.throughput * flux, count)
+    }
+}
+
+/// Trace `ray` until it hits a diffuse surface. Return its visible point and
+/// the light that doesn't come from the photons: light emitted by the surfaces
+/// found before the visible point, and light from the background found by
+/// following the path after it.
+fn find_visible_point(
+    world: &dyn Hittable,
+    ray: &Ray,
+    max_depth: u32,
+) -> (Color, Option<VisiblePoint>) {
+    let mut radiance = color!(0.0, 0.0, 0.0);
+    let mut throughput = color!(1.0, 1.0, 1.0);
+    let mut visible_point = None;
+    let mut ray = *ray;
+
+    for _ in 0..max_depth {
+        let hit = match world.hit(&ray, &HIT_RANGE) {
+            Some(hit) => hit,
+            None => {
+                radiance += throughput * background(&ray);
+                break;
+            }
+        };
+
+        // After the visible point, the light coming from the lights is
+        // already taken into account by the photons
+        if visible_point.is_none() {
+            radiance += throughput * hit.material.emitted(&hit);
+            let wo = -ray.direction;
+            if is_diffuse(&hit, wo) {
+                visible_point = Some(VisiblePoint {
+                    hit: hit.clone(),
+                    wo,
+                    throughput,
+                });
+            }
+        }
+
+        match hit.material.scatter(&ray, &hit) {
+            Some((scattered_ray, attenuation)) => {
+                throughput = throughput * attenuation;
+                ray = scattered_ray;
+            }
+            None => break,
+        }
+    }
+    (radiance, visible_point)
+}
+
+/// Photon mapping integrator. When it is created, photons are shot from the
+/// lights and stored wherever they hit diffuse surfaces. Camera rays are
+/// then traced until they hit a diffuse surface, where the photons around
+/// the hit point are gathered.
+///
+/// This makes it good at rendering caustics, although they will be somewhat
+/// blurred depending on the gather radius.
+pub struct PhotonMapping {
+    photons: KdTree<Photon>,
+    photon_count: usize,
+    gather_radius: f64,
+    max_depth: u32,
+}
+
+impl PhotonMapping {
+    /// Shoot `photon_count` photons from the lights of `world`. Photons and
+    /// camera rays bounce at most `max_depth` times, and photons are gathered
+    /// at a distance of at most `gather_radius`.
+    pub fn new(
+        world: &dyn Hittable,
+        photon_count: usize,
+        gather_radius: f64,
+        max_depth: u32,
+    ) -> PhotonMapping {
+        PhotonMapping {
+            photons: shoot_photons(world, photon_count, max_depth),
+            photon_count,
+            gather_radius,
+            max_depth,
+        }
+    }
+}
+
+impl Integrator for PhotonMapping {
+    fn radiance(&self, ray: &Ray, world: &dyn Hittable) -> Color {
+        let (mut radiance, visible_point) = find_visible_point(world, ray, self.max_depth);
+        if let Some(visible_point) = visible_point {
+            let (flux, _) = visible_point.gather(&self.photons, self.gather_radius);
+            let area = PI * self.gather_radius * self.gather_radius;
+            radiance += flux / (area * self.photon_count.max(1) as f64);
+        }
+        radiance
+    }
+}
+
+/// Statistics of the photons gathered by a pixel in stochastic progressive
+/// photon mapping
+struct PixelStatistics {
+    /// Current gather radius
+    radius: f64,
+    /// Number of photons gathered so far, reduced as the radius shrinks
+    photons: f64,
+    /// Flux gathered so far inside the current radius
+    flux: Color,
+    /// Sum of the light of every iteration that doesn't come from photons
+    direct: Color,
+}
+
+/// Stochastic progressive photon mapping. Each iteration traces a new camera
+/// ray per pixel and shoots a new batch of photons, which are gathered by the
+/// visible point of each pixel. The gather radius of each pixel shrinks with
+/// each iteration in which it gathers photons, so that the image converges
+/// to the right result without having to choose a fixed gather radius.
+pub struct ProgressivePhotonMapping {
+    iterations: u32,
+    photons_per_iteration: usize,
+    initial_radius: f64,
+    max_depth: u32,
+}
+
+impl ProgressivePhotonMapping {
+    /// Fraction of the new photons that are kept in each iteration. Smaller
+    /// values make the radius shrink faster.
+    const ALPHA: f64 = 2.0 / 3.0;
+
+    pub fn new(
+        iterations: u32,
+        photons_per_iteration: usize,
+        initial_radius: f64,
+        max_depth: u32,
+    ) -> ProgressivePhotonMapping {
+        ProgressivePhotonMapping {
+            iterations,
+            photons_per_iteration,
+            initial_radius,
+            max_depth,
+        }
+    }
+
+    /// Render `world` as seen from `camera` and return it as a PPM image
+    pub fn create_image(
+        &self,
+        world: &dyn Hittable,
+        camera: &Camera,
+        image_width: usize,
+    ) -> String {
+        let image_height: usize = (image_width as f64 / camera.aspect_ratio()) as usize;
+
+        let mut pixels: Vec<PixelStatistics> = (0..image_width * image_height)
+            .map(|_| PixelStatistics {
+                radius: self.initial_radius,
+                photons: 0.0,
+                flux: color!(0.0, 0.0, 0.0),
+                direct: color!(0.0, 0.0, 0.0),
+            })
+            .collect();
+
+        for _ in 0..self.iterations {
+            let photons = shoot_photons(world, self.photons_per_iteration, self.max_depth);
+
+            for (idx, pixel) in pixels.iter_mut().enumerate() {
+                let (x, y) = (idx % image_width, idx / image_width);
+                let u = ((x as f64) + random_num()) / (image_width as f64);
+                let v = ((y as f64) + random_num()) / (image_height as f64);
+                let (direct, visible_point) = match camera.get_weighted_ray(u, v) {
+                    Some((ray, weight)) => {
+                        let (direct, visible_point) =
+                            find_visible_point(world, &ray, self.max_depth);
+                        let visible_point = visible_point.map(|mut point| {
+                            point.throughput *= weight;
+                            point
+                        });
+                        (weight * direct, visible_point)
+                    }
+                    None => continue,
+                };
+                pixel.direct += direct;
+
+                let (flux, count) = match visible_point {
+                    Some(visible_point) => visible_point.gather(&photons, pixel.radius),
+                    None => continue,
+                };
+                if count == 0 {
+                    continue;
+                }
+
+                // Keep only a fraction of the new photons, and shrink the
+                // radius so that the density of photons stays the same
+                let new_photons = pixel.photons + ProgressivePhotonMapping::ALPHA * count as f64;
+                let new_radius =
+                    pixel.radius * (new_photons / (pixel.photons + count as f64)).sqrt();
+                let area_ratio = (new_radius * new_radius) / (pixel.radius * pixel.radius);
+                pixel.flux = (pixel.flux + flux) * area_ratio;
+                pixel.photons = new_photons;
+                pixel.radius = new_radius;
+            }
+        }
+
+        let photons_shot = (self.iterations as usize * self.photons_per_iteration).max(1) as f64;
+        let mut colors: Vec<Color> = pixels
+            .iter()
+            .map(|pixel| {
+                let area = PI * pixel.radius * pixel.radius;
+                pixel.direct / self.iterations as f64 + pixel.flux / (area * photons_shot)
+            })
+            .collect();
+        camera.expose(&mut colors);
+        write_image(&colors, image_width)
+    }
+}
@@ -0,0 +1,394 @@
+use crate::{
+    color,
+    color::Color,
+    hittable::HitRecord,
+    material::{Material, RoughDielectric},
+    microfacet::{fresnel_schlick, Gtr1, TrowbridgeReitz},
+    ray::Ray,
+    rtweekend::{clamp, random_num, PI},
+    vector3::Vec3,
+};
+
+/// Linear interpolation between `a` and `b`
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    (1.0 - t) * a + t * b
+}
+
+/// Lobes of the principled BSDF that can be sampled
+#[derive(Clone, Copy)]
+enum Lobe {
+    Diffuse,
+    Specular,
+    Clearcoat,
+    Transmission,
+}
+
+/// Material based on the principled BSDF by Burley ("Physically Based
+/// Shading at Disney", 2012 and 2015), whose parameters are the ones artists
+/// use in most modeling tools. All parameters but `base_color` and `ior` are
+/// in the range [0.0, 1.0].
+///
+/// Its BSDF is the sum of several lobes: a diffuse one with retro-reflection
+/// and sheen, a specular one, a clear coat and a rough dielectric for
+/// transmission. `scatter` chooses one of them at random and then weights the
+/// sampled direction with the BSDF and pdf of every lobe.
+pub struct Principled {
+    base_color: Color,
+    metallic: f64,
+    roughness: f64,
+    specular: f64,
+    clearcoat: f64,
+    clearcoat_gloss: f64,
+    sheen: f64,
+    sheen_tint: f64,
+    transmission: f64,
+    ior: f64,
+}
+
+impl Principled {
+    /// Create a principled material with a given base color. Every other
+    /// parameter has its default value, which makes it a dielectric with
+    /// medium roughness.
+    pub fn new(base_color: Color) -> Principled {
+        Principled {
+            base_color,
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            transmission: 0.0,
+            ior: 1.5,
+        }
+    }
+    /// How much the material behaves like a metal
+    pub fn metallic(mut self, metallic: f64) -> Principled {
+        self.metallic = clamp(metallic, 0.0..=1.0);
+        self
+    }
+    /// Roughness of the surface. Values below 0.05 are treated as 0.05, so
+    /// that the material can always be evaluated.
+    pub fn roughness(mut self, roughness: f64) -> Principled {
+        self.roughness = clamp(roughness, 0.05..=1.0);
+        self
+    }
+    /// Amount of specular reflection of dielectrics. The default 0.5 is a
+    /// reflectance of 4% at normal incidence.
+    pub fn specular(mut self, specular: f64) -> Principled {
+        self.specular = clamp(specular, 0.0..=1.0);
+        self
+    }
+    /// Strength of a transparent coat over the surface
+    pub fn clearcoat(mut self, clearcoat: f64) -> Principled {
+        self.clearcoat = clamp(clearcoat, 0.0..=1.0);
+        self
+    }
+    /// How glossy the clear coat is
+    pub fn clearcoat_gloss(mut self, clearcoat_gloss: f64) -> Principled {
+        self.clearcoat_gloss = clamp(clearcoat_gloss, 0.0..=1.0);
+        self
+    }
+    /// Amount of soft reflection at grazing angles, as in cloth
+    pub fn sheen(mut self, sheen: f64) -> Principled {
+        self.sheen = clamp(sheen, 0.0..=1.0);
+        self
+    }
+    /// How much the sheen is tinted by the base color
+    pub fn sheen_tint(mut self, sheen_tint: f64) -> Principled {
+        self.sheen_tint = clamp(sheen_tint, 0.0..=1.0);
+        self
+    }
+    /// How much light goes through the material, as in glass
+    pub fn transmission(mut self, transmission: f64) -> Principled {
+        self.transmission = clamp(transmission, 0.0..=1.0);
+        self
+    }
+    /// Refraction index used for transmission
+    pub fn ior(mut self, ior: f64) -> Principled {
+        self.ior = ior;
+        self
+    }
+
+    /// Base color normalized to a luminance of 1.0, used to tint other lobes
+    fn tint(&self) -> Color {
+        let luminance = self.base_color.luminance();
+        if luminance > 0.0 {
+            self.base_color / luminance
+        } else {
+            color!(1.0, 1.0, 1.0)
+        }
+    }
+
+    fn diffuse_weight(&self) -> f64 {
+        (1.0 - self.metallic) * (1.0 - self.transmission)
+    }
+
+    fn transmission_weight(&self) -> f64 {
+        (1.0 - self.metallic) * self.transmission
+    }
+
+    fn specular_distribution(&self) -> TrowbridgeReitz {
+        TrowbridgeReitz::new(self.roughness)
+    }
+
+    fn clearcoat_distribution(&self) -> Gtr1 {
+        Gtr1::new(0.1 + (0.001 - 0.1) * self.clearcoat_gloss)
+    }
+
+    fn dielectric(&self) -> RoughDielectric {
+        RoughDielectric::new(self.ior, self.roughness)
+    }
+
+    /// Probability of sampling each lobe, for light leaving towards `wo`.
+    /// They are all 0.0 if no lobe scatters light in that direction.
+    fn lobe_probabilities(&self, wo: Vec3) -> [(Lobe, f64); 4] {
+        // Inside the material, only transmission is possible, and an opaque
+        // material absorbs the light
+        let outside = wo.z > 0.0;
+        let weights = if outside {
+            [
+                self.diffuse_weight(),
+                1.0 - self.transmission_weight(),
+                0.25 * self.clearcoat,
+                self.transmission_weight(),
+            ]
+        } else {
+            [0.0, 0.0, 0.0, self.transmission_weight()]
+        };
+        let total: f64 = weights.iter().sum();
+        let weights = if total > 0.0 {
+            weights.map(|weight| weight / total)
+        } else {
+            [0.0; 4]
+        };
+        [
+            (Lobe::Diffuse, weights[0]),
+            (Lobe::Specular, weights[1]),
+            (Lobe::Clearcoat, weights[2]),
+            (Lobe::Transmission, weights[3]),
+        ]
+    }
+
+    /// Return the BSDF of a lobe, for directions given in local coordinates
+    /// whose Z axis points outside of the material
+    fn lobe_eval(&self, lobe: Lobe, wo: Vec3, wi: Vec3) -> Color {
+        let black = color!(0.0, 0.0, 0.0);
+        let reflection = wo.z > 0.0 && wi.z > 0.0;
+        let wh = (wo + wi).unit_vec();
+
+        match lobe {
+            Lobe::Diffuse if reflection => {
+                // Burley's diffuse, with retro-reflection at grazing angles
+                let cos_d = wi.dot(wh);
+                let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+                let fl = fresnel_schlick(1.0, wi.z) * (fd90 - 1.0) + 1.0;
+                let fv = fresnel_schlick(1.0, wo.z) * (fd90 - 1.0) + 1.0;
+                let diffuse = self.base_color * (fl * fv / PI);
+
+                let sheen_color = lerp(color!(1.0, 1.0, 1.0), self.tint(), self.sheen_tint);
+                let sheen = sheen_color * (self.sheen * (1.0 - cos_d).powi(5));
+                (diffuse + sheen) * self.diffuse_weight()
+            }
+            Lobe::Specular if reflection => {
+                let dielectric_r0 = self.specular * 0.08 * color!(1.0, 1.0, 1.0);
+                let r0 = lerp(dielectric_r0, self.base_color, self.metallic);
+                let cos_d = wo.dot(wh);
+                let fresnel = color!(
+                    fresnel_schlick(r0.red, cos_d),
+                    fresnel_schlick(r0.green, cos_d),
+                    fresnel_schlick(r0.blue, cos_d)
+                );
+                let distribution = self.specular_distribution();
+                let d = distribution.d(wh);
+                let g = distribution.g(wo, wi);
+                fresnel * (d * g / (4.0 * wo.z * wi.z)) * (1.0 - self.transmission_weight())
+            }
+            Lobe::Clearcoat if reflection => {
+                let fresnel = fresnel_schlick(0.04, wo.dot(wh));
+                let d = self.clearcoat_distribution().d(wh);
+                let g = TrowbridgeReitz::new(0.5).g(wo, wi);
+                let f = 0.25 * self.clearcoat * fresnel * d * g / (4.0 * wo.z * wi.z);
+                color!(f, f, f)
+            }
+            Lobe::Transmission => {
+                let f = self.dielectric().local_eval(wo, wi);
+                // Refracted light is tinted when entering and when leaving
+                let tint = if wo.z * wi.z < 0.0 {
+                    color!(
+                        self.base_color.red.sqrt(),
+                        self.base_color.green.sqrt(),
+                        self.base_color.blue.sqrt()
+                    )
+                } else {
+                    color!(1.0, 1.0, 1.0)
+                };
+                tint * (f * self.transmission_weight())
+            }
+            _ => black,
+        }
+    }
+
+    /// Return the probability density of sampling `wi` with a lobe, for
+    /// directions given in local coordinates
+    fn lobe_pdf(&self, lobe: Lobe, wo: Vec3, wi: Vec3) -> f64 {
+        let reflection = wo.z > 0.0 && wi.z > 0.0;
+        let wh = (wo + wi).unit_vec();
+
+        match lobe {
+            Lobe::Diffuse if reflection => wi.z / PI,
+            Lobe::Specular if reflection => {
+                self.specular_distribution().pdf_visible_normal(wo, wh) / (4.0 * wo.dot(wh))
+            }
+            Lobe::Clearcoat if reflection => {
+                self.clearcoat_distribution().pdf_normal(wh) / (4.0 * wo.dot(wh))
+            }
+            Lobe::Transmission => self.dielectric().local_pdf(wo, wi),
+            _ => 0.0,
+        }
+    }
+
+    /// Sample a direction with a lobe, for directions given in local coordinates
+    fn lobe_sample(&self, lobe: Lobe, wo: Vec3) -> Option<Vec3> {
+        match lobe {
+            Lobe::Diffuse => Some(Vec3::random_cosine_direction()),
+            Lobe::Specular => {
+                let wm = self.specular_distribution().sample_visible_normal(wo);
+                Some((-wo).reflect(wm))
+            }
+            Lobe::Clearcoat => {
+                let wm = self.clearcoat_distribution().sample_normal();
+                Some((-wo).reflect(wm))
+            }
+            Lobe::Transmission => self.dielectric().local_sample(wo),
+        }
+    }
+
+    /// BSDF for directions given in local coordinates
+    fn local_eval(&self, wo: Vec3, wi: Vec3) -> Color {
+        [
+            Lobe::Diffuse,
+            Lobe::Specular,
+            Lobe::Clearcoat,
+            Lobe::Transmission,
+        ]
+        .into_iter()
+        .fold(color!(0.0, 0.0, 0.0), |f, lobe| {
+            f + self.lobe_eval(lobe, wo, wi)
+        })
+    }
+
+    /// Probability density of `scatter` choosing `wi`, for directions given
+    /// in local coordinates
+    fn local_pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        self.lobe_probabilities(wo)
+            .into_iter()
+            .filter(|&(_, probability)| probability > 0.0)
+            .map(|(lobe, probability)| probability * self.lobe_pdf(lobe, wo, wi))
+            .sum()
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
+        let basis = RoughDielectric::basis(hit);
+        let wo = basis.world_to_local(-ray_in.direction);
+
+        // Choose a lobe according to its probability, or absorb the light if
+        // no lobe scatters it. Rounding can leave `chosen` past the last lobe.
+        let probabilities = self.lobe_probabilities(wo);
+        let mut possible = probabilities
+            .into_iter()
+            .filter(|&(_, probability)| probability > 0.0);
+        let mut chosen = random_num();
+        let (lobe, _) = possible
+            .clone()
+            .find(|&(_, probability)| {
+                chosen -= probability;
+                chosen < 0.0
+            })
+            .or_else(|| possible.next_back())?;
+
+        let wi = self.lobe_sample(lobe, wo)?;
+        let pdf = self.local_pdf(wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let direction = basis.local_to_world(wi);
+        if !hit.same_side(direction) {
+            return None;
+        }
+        let attenuation = self.local_eval(wo, wi) * (wi.z.abs() / pdf);
+        Some((Ray::new(hit.point, direction), attenuation))
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, hit: &HitRecord) -> Option<Color> {
+        if !hit.same_side(wi) {
+            return Some(color!(0.0, 0.0, 0.0));
+        }
+        let basis = RoughDielectric::basis(hit);
+        Some(self.local_eval(basis.world_to_local(wo), basis.world_to_local(wi)))
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, hit: &HitRecord) -> f64 {
+        let basis = RoughDielectric::basis(hit);
+        self.local_pdf(basis.world_to_local(wo), basis.world_to_local(wi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::vec3;
+
+    /// Directions of light leaving towards the inside of the material, and
+    /// arriving from the outside through the surface
+    fn refracted_directions() -> (Vec3, Vec3) {
+        (
+            vec3!(0.3, 0.0, -1.0).unit_vec(),
+            vec3!(-0.2, 0.0, 1.0).unit_vec(),
+        )
+    }
+
+    #[test]
+    fn opaque_surfaces_absorb_light_from_inside() {
+        for material in [
+            Principled::new(color!(0.8, 0.8, 0.8)),
+            Principled::new(color!(0.8, 0.8, 0.8))
+                .transmission(1.0)
+                .metallic(1.0),
+        ] {
+            let (wo, wi) = refracted_directions();
+            let f = material.local_eval(wo, wi);
+            assert_eq!((f.red, f.green, f.blue), (0.0, 0.0, 0.0));
+            assert_eq!(material.local_pdf(wo, wi), 0.0);
+
+            // A ray that leaves the inside of a closed object
+            let ray = Ray::new(vec3!(0.0, 0.0, -0.5), vec3!(0.1, 0.0, 1.0));
+            let material = Rc::new(material);
+            let hit = HitRecord::new(
+                Vec3::zero(),
+                vec3!(0.0, 0.0, 1.0),
+                0.5,
+                &ray,
+                material.clone(),
+                (0.0, 0.0),
+            );
+            assert!(!hit.front_face);
+            for _ in 0..100 {
+                assert!(material.scatter(&ray, &hit).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn transmissive_surfaces_refract_light_from_inside() {
+        let material = Principled::new(color!(0.8, 0.8, 0.8)).transmission(1.0);
+        let (wo, wi) = refracted_directions();
+        assert!(material.local_eval(wo, wi).luminance() > 0.0);
+        assert!(material.local_pdf(wo, wi) > 0.0);
+    }
+}
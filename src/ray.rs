@@ -6,6 +6,9 @@ use crate::vector3::Vec3;
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
+    /// Wavelength in nanometers of the light carried by the ray, when
+    /// rendering in spectral mode
+    pub wavelength: Option<f64>,
 }
 
 impl Ray {
@@ -15,9 +18,16 @@ impl Ray {
         Ray {
             origin,
             direction: direction.unit_vec(),
+            wavelength: None,
         }
     }
 
+    /// Return the same ray, but carrying light of the given wavelength
+    pub fn with_wavelength(mut self, wavelength: f64) -> Ray {
+        self.wavelength = Some(wavelength);
+        self
+    }
+
     // Point where the ray is at given a distance from its origin point.
     pub fn at(self, distance: f64) -> Vec3 {
         self.origin + distance * self.direction
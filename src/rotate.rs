@@ -0,0 +1,119 @@
+use std::ops::Range;
+
+use crate::{
+    angle::Degrees,
+    hittable::{HitRecord, Hittable},
+    ray::Ray,
+    vector3::Vec3,
+};
+
+/// Structure rotated around an axis through the origin. Positive angles turn
+/// counterclockwise when looking at the origin from the tip of the axis.
+///
+/// Lights inside are sampled with the `sample_surface` of the rotated
+/// structure, so rotate each light by itself rather than a list of them.
+pub struct Rotate<H> {
+    object: H,
+    /// Unit vector along the axis of rotation
+    axis: Vec3,
+    sin_angle: f64,
+    cos_angle: f64,
+}
+
+impl<H: Hittable> Rotate<H> {
+    pub fn new(object: H, axis: Vec3, angle: Degrees) -> Rotate<H> {
+        Rotate {
+            object,
+            axis: axis.unit_vec(),
+            sin_angle: angle.sin(),
+            cos_angle: angle.cos(),
+        }
+    }
+
+    /// Rotate `v` by the angle of the rotation, or by its opposite when
+    /// `inverse` is true, with Rodrigues' rotation formula
+    fn rotate(&self, v: Vec3, inverse: bool) -> Vec3 {
+        let sin_angle = if inverse {
+            -self.sin_angle
+        } else {
+            self.sin_angle
+        };
+        let axis = self.axis;
+        v * self.cos_angle
+            + axis.cross(v) * sin_angle
+            + axis * (axis.dot(v) * (1.0 - self.cos_angle))
+    }
+
+    /// Rotate every point and direction of a hit on the structure before
+    /// its rotation
+    fn rotate_hit(&self, mut hit: HitRecord) -> HitRecord {
+        hit.point = self.rotate(hit.point, false);
+        hit.normal = self.rotate(hit.normal, false);
+        hit.shading_normal = self.rotate(hit.shading_normal, false);
+        hit.dpdu = self.rotate(hit.dpdu, false);
+        hit.dpdv = self.rotate(hit.dpdv, false);
+        hit
+    }
+
+    /// Return `ray` as seen by the structure before its rotation
+    fn unrotate_ray(&self, ray: &Ray) -> Ray {
+        Ray::new(
+            self.rotate(ray.origin, true),
+            self.rotate(ray.direction, true),
+        )
+    }
+}
+
+impl<H: Hittable> Hittable for Rotate<H> {
+    fn hit(&self, ray: &Ray, hit_range: &Range<f64>) -> Option<HitRecord> {
+        // Rotations keep distances, so the hit range stays the same
+        let hit = self.object.hit(&self.unrotate_ray(ray), hit_range)?;
+        Some(self.rotate_hit(hit))
+    }
+
+    fn intersection_tests(&self, ray: &Ray, hit_range: &Range<f64>) -> usize {
+        self.object
+            .intersection_tests(&self.unrotate_ray(ray), hit_range)
+    }
+
+    fn collect_lights<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        let mut inner_lights = vec![];
+        self.object.collect_lights(&mut inner_lights);
+        if !inner_lights.is_empty() {
+            lights.push(self);
+        }
+    }
+
+    fn area(&self) -> f64 {
+        self.object.area()
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        self.object.sample_surface().map(|hit| self.rotate_hit(hit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{color, color::Color, material::Lambertian, sphere::Sphere, vec3};
+
+    #[test]
+    fn rotates_around_the_axis() {
+        let material = Rc::new(Lambertian::new(color!(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(vec3!(2.0, 0.0, 0.0), 1.0, material);
+        let rotated = Rotate::new(sphere, vec3!(0.0, 2.0, 0.0), 90.0.into());
+
+        // A quarter turn around Y moves the sphere from +X to -Z
+        let ray = Ray::new(vec3!(5.0, 0.0, 0.0), vec3!(-1.0, 0.0, 0.0));
+        assert!(rotated.hit(&ray, &(0.001..f64::INFINITY)).is_none());
+
+        let ray = Ray::new(vec3!(0.0, 5.0, -2.0), vec3!(0.0, -1.0, 0.0));
+        let hit = rotated.hit(&ray, &(0.001..f64::INFINITY)).unwrap();
+        assert!((hit.point - vec3!(0.0, 1.0, -2.0)).length() < 1e-9);
+        assert!((hit.normal - vec3!(0.0, 1.0, 0.0)).length() < 1e-9);
+        assert!((hit.dist - 4.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    ops::{AddAssign, Mul},
+    sync::OnceLock,
+};
+
+use crate::{color, color::Color, rtweekend::random_num};
+
+/// Shortest wavelength, in nanometers, that is sampled in spectral mode
+const LAMBDA_MIN: f64 = 360.0;
+/// Longest wavelength, in nanometers, that is sampled in spectral mode
+const LAMBDA_MAX: f64 = 830.0;
+/// Number of wavelengths traced together along each path
+const SAMPLE_COUNT: usize = 4;
+
+/// Wavelengths of light traced along a path. Only the first one (the hero
+/// wavelength) is chosen at random; the others are spread evenly over the
+/// visible spectrum after it, so that a single path covers every color.
+#[derive(Clone, Copy)]
+pub struct SampledWavelengths {
+    lambda: [f64; SAMPLE_COUNT],
+    pdf: [f64; SAMPLE_COUNT],
+}
+
+impl SampledWavelengths {
+    /// Choose a random hero wavelength and the wavelengths that follow it
+    pub fn sample() -> SampledWavelengths {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = random_num() * range;
+        let lambda = std::array::from_fn(|i| {
+            let offset = i as f64 * range / SAMPLE_COUNT as f64;
+            LAMBDA_MIN + (hero + offset) % range
+        });
+        SampledWavelengths {
+            lambda,
+            pdf: [1.0 / range; SAMPLE_COUNT],
+        }
+    }
+
+    /// Return the hero wavelength, which is the one that decides where the
+    /// path goes when it depends on the wavelength
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    /// Stop tracing every wavelength but the hero one. This has to be done
+    /// once the path has been bent by a dispersive material, since the
+    /// other wavelengths would have followed different paths.
+    pub fn terminate_secondary(&mut self) {
+        if self.pdf[1] == 0.0 {
+            return;
+        }
+        // The hero wavelength now has to account for every sample by itself
+        self.pdf[0] /= SAMPLE_COUNT as f64;
+        self.pdf[1..].fill(0.0);
+    }
+}
+
+/// Value of a spectrum at each of the wavelengths of a `SampledWavelengths`
+#[derive(Clone, Copy)]
+pub struct SampledSpectrum([f64; SAMPLE_COUNT]);
+
+impl SampledSpectrum {
+    /// Create a spectrum with the same value at every wavelength
+    pub fn constant(value: f64) -> SampledSpectrum {
+        SampledSpectrum([value; SAMPLE_COUNT])
+    }
+
+    /// Return a spectrum that looks like the given color, at the given
+    /// wavelengths
+    pub fn from_rgb(color: Color, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+        SampledSpectrum(
+            wavelengths
+                .lambda
+                .map(|lambda| rgb_to_spectrum(color, lambda)),
+        )
+    }
+
+    /// Return true if the spectrum is zero at every wavelength
+    pub fn is_black(&self) -> bool {
+        self.0.iter().all(|&value| value <= 0.0)
+    }
+
+    /// Convert the spectrum to a linear sRGB color. The result is an estimate
+    /// of the color of the full spectrum, made with the sampled wavelengths.
+    pub fn to_rgb(self, wavelengths: &SampledWavelengths) -> Color {
+        let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+        for i in 0..SAMPLE_COUNT {
+            if wavelengths.pdf[i] == 0.0 {
+                continue;
+            }
+            let (x_bar, y_bar, z_bar) = color_matching(wavelengths.lambda[i]);
+            let value = self.0[i] / wavelengths.pdf[i];
+            x += value * x_bar;
+            y += value * y_bar;
+            z += value * z_bar;
+        }
+
+        let conversion = Conversion::get();
+        let scale = 1.0 / (SAMPLE_COUNT as f64 * conversion.y_integral);
+        let rgb = xyz_to_rgb(x * scale, y * scale, z * scale);
+        color!(
+            rgb.red / conversion.white.red,
+            rgb.green / conversion.white.green,
+            rgb.blue / conversion.white.blue
+        )
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        SampledSpectrum(std::array::from_fn(|i| self.0[i] * rhs.0[i]))
+    }
+}
+
+impl AddAssign for SampledSpectrum {
+    fn add_assign(&mut self, rhs: Self) {
+        for i in 0..SAMPLE_COUNT {
+            self.0[i] += rhs.0[i];
+        }
+    }
+}
+
+/// How the refraction index of a material changes with the wavelength of
+/// light. Coefficients use wavelengths in micrometers, like optical glass
+/// catalogs do.
+#[derive(Clone, Copy)]
+pub enum Dispersion {
+    /// Cauchy's equation: `n = a + b / λ²`
+    Cauchy { a: f64, b: f64 },
+    /// Sellmeier's equation: `n² = 1 + Σ b[i] λ² / (λ² - c[i])`
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    /// Borosilicate crown glass, the most common optical glass
+    pub const BK7: Dispersion = Dispersion::Sellmeier {
+        b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+        c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+    };
+    /// Dense flint glass, which disperses light much more than crown glass
+    pub const FLINT: Dispersion = Dispersion::Cauchy {
+        a: 1.7280,
+        b: 0.01342,
+    };
+    /// Diamond
+    pub const DIAMOND: Dispersion = Dispersion::Sellmeier {
+        b: [0.3306, 4.3356, 0.0],
+        c: [0.030_625, 0.011_236, 0.0],
+    };
+
+    /// Wavelength in nanometers at which the refraction index of a material
+    /// is usually given (the sodium D line)
+    pub const REFERENCE_WAVELENGTH: f64 = 587.6;
+
+    /// Return the refraction index for light of a given wavelength in nanometers
+    pub fn refraction_idx(&self, wavelength: f64) -> f64 {
+        let lambda = wavelength / 1000.0;
+        let lambda2 = lambda * lambda;
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / lambda2,
+            Dispersion::Sellmeier { b, c } => (1.0
+                + (0..3)
+                    .map(|i| b[i] * lambda2 / (lambda2 - c[i]))
+                    .sum::<f64>())
+            .sqrt(),
+        }
+    }
+}
+
+/// Basis spectra from "An RGB to Spectrum Conversion for Reflectances" by
+/// Smits (1999), sampled in 10 bins between 380 and 720 nanometers
+mod smits {
+    pub const WHITE: [f64; 10] = [
+        1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+    ];
+    pub const CYAN: [f64; 10] = [
+        0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+    ];
+    pub const MAGENTA: [f64; 10] = [
+        1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+    ];
+    pub const YELLOW: [f64; 10] = [
+        0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+    ];
+    pub const RED: [f64; 10] = [
+        0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+    ];
+    pub const GREEN: [f64; 10] = [
+        0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0000,
+    ];
+    pub const BLUE: [f64; 10] = [
+        1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+    ];
+}
+
+/// Return the value at `lambda` of a smooth spectrum that looks like `color`.
+/// The spectrum is made of a white part plus the secondary and primary colors
+/// needed to reach `color`, as described by Smits.
+fn rgb_to_spectrum(color: Color, lambda: f64) -> f64 {
+    let bin = (((lambda - 380.0) / 34.0).floor().max(0.0) as usize).min(9);
+    let Color { red, green, blue } = color;
+
+    if red <= green && red <= blue {
+        let rest = if green <= blue {
+            smits::CYAN[bin] * (green - red) + smits::BLUE[bin] * (blue - green)
+        } else {
+            smits::CYAN[bin] * (blue - red) + smits::GREEN[bin] * (green - blue)
+        };
+        smits::WHITE[bin] * red + rest
+    } else if green <= red && green <= blue {
+        let rest = if red <= blue {
+            smits::MAGENTA[bin] * (red - green) + smits::BLUE[bin] * (blue - red)
+        } else {
+            smits::MAGENTA[bin] * (blue - green) + smits::RED[bin] * (red - blue)
+        };
+        smits::WHITE[bin] * green + rest
+    } else {
+        let rest = if red <= green {
+            smits::YELLOW[bin] * (red - blue) + smits::GREEN[bin] * (green - red)
+        } else {
+            smits::YELLOW[bin] * (green - blue) + smits::RED[bin] * (red - green)
+        };
+        smits::WHITE[bin] * blue + rest
+    }
+}
+
+/// Return the CIE 1931 color matching functions at a wavelength in
+/// nanometers, using the multi-lobe fit by Wyman, Sloan and Shirley in
+/// "Simple Analytic Approximations to the CIE XYZ Color Matching Functions"
+/// (2013)
+fn color_matching(lambda: f64) -> (f64, f64, f64) {
+    let g = |mu: f64, sigma_low: f64, sigma_high: f64| {
+        let sigma = if lambda < mu { sigma_low } else { sigma_high };
+        let t = (lambda - mu) / sigma;
+        (-0.5 * t * t).exp()
+    };
+    let x =
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2);
+    let y = 0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1);
+    let z = 1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+/// Convert a CIE XYZ color to linear sRGB
+fn xyz_to_rgb(x: f64, y: f64, z: f64) -> Color {
+    color!(
+        3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+        -0.969_266_0 * x + 1.876_010_8 * y + 0.041_556_0 * z,
+        0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z
+    )
+}
+
+/// Constants needed to convert spectra to colors
+struct Conversion {
+    /// Integral of the Y color matching function, which is the luminance of
+    /// a spectrum that has a value of 1 at every wavelength
+    y_integral: f64,
+    /// Color of that same spectrum. Colors are divided by it, so that
+    /// spectra made from white colors are still white.
+    white: Color,
+}
+
+impl Conversion {
+    fn get() -> &'static Conversion {
+        static CONVERSION: OnceLock<Conversion> = OnceLock::new();
+        CONVERSION.get_or_init(|| {
+            let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+            let mut lambda = LAMBDA_MIN;
+            while lambda <= LAMBDA_MAX {
+                let (x_bar, y_bar, z_bar) = color_matching(lambda);
+                x += x_bar;
+                y += y_bar;
+                z += z_bar;
+                lambda += 1.0;
+            }
+            Conversion {
+                y_integral: y,
+                white: xyz_to_rgb(x / y, 1.0, z / y),
+            }
+        })
+    }
+}
@@ -4,6 +4,8 @@ use crate::{
     hittable::{HitRecord, Hittable},
     material::Material,
     ray::Ray,
+    rtweekend::{clamp, random_num, PI},
+    vec3,
     vector3::Vec3,
 };
 
@@ -21,6 +23,31 @@ impl Sphere {
             material,
         }
     }
+
+    /// Return the surface coordinates of a point in the unit sphere, where:
+    /// - `u` is the angle around the Y axis from X=-1, mapped to [0.0, 1.0].
+    /// - `v` is the angle from Y=-1 to Y=+1, mapped to [0.0, 1.0].
+    fn uv(point: Vec3) -> (f64, f64) {
+        let theta = clamp(-point.y, -1.0..=1.0).acos();
+        let phi = (-point.z).atan2(point.x) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    /// Return the derivatives of a point of the sphere with respect to its
+    /// surface coordinates, given the point in the unit sphere
+    fn tangents(&self, point: Vec3) -> (Vec3, Vec3) {
+        let radius = self.radius.abs();
+        let sin_theta = (1.0 - point.y * point.y).max(0.0).sqrt().max(1e-9);
+        let dpdu = 2.0 * PI * radius * vec3!(point.z, 0.0, -point.x);
+        let dpdv = PI
+            * radius
+            * vec3!(
+                -point.x * point.y / sin_theta,
+                sin_theta,
+                -point.y * point.z / sin_theta
+            );
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
@@ -46,25 +73,97 @@ impl Hittable for Sphere {
 
         let sqrt_d = discriminant.sqrt();
 
-        let dist = {
-            let neg_t = (-half_b - sqrt_d) / a;
-            let pos_t = (-half_b + sqrt_d) / a;
-            if hit_range.contains(&neg_t) {
-                neg_t
-            } else if hit_range.contains(&pos_t) {
-                pos_t
-            } else {
-                return None;
-            }
-        };
-
-        let hit_point = ray.at(dist);
-        Some(HitRecord::new(
-            hit_point,
-            (hit_point - self.center) / self.radius,
-            dist,
-            ray,
+        // If the nearest hit is cut out, the ray can still hit the far side
+        // of the sphere
+        let neg_t = (-half_b - sqrt_d) / a;
+        let pos_t = (-half_b + sqrt_d) / a;
+        [neg_t, pos_t]
+            .into_iter()
+            .filter(|dist| hit_range.contains(dist))
+            .map(|dist| {
+                let hit_point = ray.at(dist);
+                let unit_point = (hit_point - self.center) / self.radius.abs();
+                let (dpdu, dpdv) = self.tangents(unit_point);
+                HitRecord::new(
+                    hit_point,
+                    (hit_point - self.center) / self.radius,
+                    dist,
+                    ray,
+                    self.material.clone(),
+                    Sphere::uv(unit_point),
+                )
+                .with_tangents(dpdu, dpdv)
+            })
+            .find(|hit| hit.passes_alpha_test(ray))
+    }
+
+    fn collect_lights<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        if self.material.emits_light() {
+            lights.push(self);
+        }
+    }
+
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        let direction = Vec3::random_unit_vec();
+        let point = self.center + self.radius.abs() * direction;
+        let outward_normal = direction * self.radius.signum();
+        // A ray coming from outside the sphere towards the sampled point
+        let ray = Ray::new(point + outward_normal, -outward_normal);
+        let (dpdu, dpdv) = self.tangents(direction);
+        let hit = HitRecord::new(
+            point,
+            outward_normal,
+            0.0,
+            &ray,
             self.material.clone(),
-        ))
+            Sphere::uv(direction),
+        )
+        .with_tangents(dpdu, dpdv);
+        // Points that are cut out don't emit light
+        Some(hit).filter(|hit| random_num() < hit.material.opacity(hit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color, color::Color, cutout::Cutout, material::Lambertian, texture::SolidColor};
+
+    /// Sphere at the origin whose material has the given opacity
+    fn cutout_sphere(opacity: f64) -> Sphere {
+        let opacity = Rc::new(SolidColor::new(color!(opacity, opacity, opacity)));
+        let material = Rc::new(Lambertian::new(color!(0.5, 0.5, 0.5)));
+        Sphere::new(Vec3::zero(), 1.0, Rc::new(Cutout::new(opacity, material)))
+    }
+
+    #[test]
+    fn alpha_test_is_the_same_for_the_same_ray() {
+        let sphere = cutout_sphere(0.5);
+        let mut hits = 0;
+        for i in 0..1000 {
+            let ray = Ray::new(vec3!(0.001 * i as f64, 0.0, 5.0), vec3!(0.0, 0.0, -1.0));
+            let hit = sphere
+                .hit(&ray, &(0.001..f64::INFINITY))
+                .map(|hit| hit.dist);
+            for _ in 0..4 {
+                let again = sphere
+                    .hit(&ray, &(0.001..f64::INFINITY))
+                    .map(|hit| hit.dist);
+                assert_eq!(hit, again);
+            }
+            // Either side of the sphere can be hit
+            hits += hit.is_some() as usize;
+        }
+        assert!((650..850).contains(&hits), "{hits} hits");
+    }
+
+    #[test]
+    fn transparent_surfaces_are_not_sampled() {
+        assert!((0..100).all(|_| cutout_sphere(0.0).sample_surface().is_none()));
+        assert!((0..100).all(|_| cutout_sphere(1.0).sample_surface().is_some()));
     }
 }
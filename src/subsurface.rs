@@ -0,0 +1,178 @@
+use crate::{
+    color, color::Color, hittable::HitRecord, material::Material, microfacet::fresnel_dielectric,
+    ray::Ray, rtweekend::random_num,
+};
+
+/// Return the components of a color as an array
+fn channels(color: Color) -> [f64; 3] {
+    [color.red, color.green, color.blue]
+}
+
+/// Participating medium that fills the inside of an object, where light
+/// travels a random distance before being scattered in a random direction or
+/// absorbed
+#[derive(Clone, Copy)]
+pub struct Medium {
+    /// Probability per unit distance of light interacting with the medium
+    sigma_t: Color,
+    /// Fraction of the interactions in which light is scattered instead of
+    /// absorbed
+    albedo: Color,
+}
+
+impl Medium {
+    /// Start a random walk of light through the medium
+    pub fn start_walk(self) -> RandomWalk {
+        RandomWalk {
+            medium: self,
+            channel: ((random_num() * 3.0) as usize).min(2),
+            weight: color!(1.0, 1.0, 1.0),
+            pdf_ratios: [1.0; 3],
+        }
+    }
+}
+
+/// Light traveling through a `Medium` between two surfaces, where it can be
+/// scattered any number of times.
+///
+/// Each color channel has its own distances between interactions, so they
+/// are all sampled with the distances of a random channel. Light is weighted
+/// by the probability of every channel sampling the whole walk, instead of
+/// each step of it, since the weights of the steps would get very different
+/// from each other after the hundreds of steps of a random walk.
+#[derive(Clone, Copy)]
+pub struct RandomWalk {
+    medium: Medium,
+    /// Channel whose distances are sampled
+    channel: usize,
+    /// Product of the attenuations of each step divided by their probability
+    /// for the sampled channel
+    weight: Color,
+    /// Product of the probability of each step for each channel, divided by
+    /// their probability for the sampled channel
+    pdf_ratios: [f64; 3],
+}
+
+impl RandomWalk {
+    /// Sample where light traveling along a segment of length `max_distance`
+    /// interacts with the medium. Return the distance to the interaction, or
+    /// `None` if light reaches the end of the segment.
+    pub fn sample(&mut self, max_distance: f64) -> Option<f64> {
+        let sigma_t = channels(self.medium.sigma_t);
+        let distance = -(1.0 - random_num()).ln() / sigma_t[self.channel];
+
+        let scattered = distance < max_distance;
+        let distance = distance.min(max_distance);
+        let transmittance = sigma_t.map(|sigma| (-sigma * distance).exp());
+
+        // Probability of each channel sampling this step
+        let pdfs = if scattered {
+            [0, 1, 2].map(|i| sigma_t[i] * transmittance[i])
+        } else {
+            transmittance
+        };
+        let [r, g, b] = transmittance;
+        let attenuation = if scattered {
+            color!(r, g, b) * self.medium.sigma_t * self.medium.albedo
+        } else {
+            color!(r, g, b)
+        };
+
+        let pdf = pdfs[self.channel];
+        self.weight = self.weight * attenuation / pdf;
+        for (ratio, channel_pdf) in self.pdf_ratios.iter_mut().zip(pdfs) {
+            *ratio *= channel_pdf / pdf;
+        }
+        scattered.then_some(distance)
+    }
+
+    /// Return how much the light has to be attenuated by the walk so far,
+    /// and start a new walk through the same medium
+    pub fn finish(&mut self) -> Color {
+        let average = (self.pdf_ratios[0] + self.pdf_ratios[1] + self.pdf_ratios[2]) / 3.0;
+        let weight = self.weight / average;
+        *self = self.medium.start_walk();
+        weight
+    }
+}
+
+/// Material for translucent objects like skin, wax, marble or milk, where
+/// light goes through the surface and bounces many times inside the object
+/// before coming out somewhere else.
+///
+/// The surface is a smooth dielectric, and the inside of the object is a
+/// `Medium` where light takes a random walk. The object must be closed, and
+/// only integrators that follow rays through media (like `PathTracer`) render
+/// the random walk; other ones see it as clear glass.
+pub struct Subsurface {
+    refraction_idx: f64,
+    medium: Medium,
+}
+
+impl Subsurface {
+    /// Create a material whose color seen from outside is `albedo`, and where
+    /// light of each color travels on average `mean_free_path` before
+    /// interacting with the inside of the object. Larger distances make the
+    /// object look more translucent.
+    ///
+    /// The albedo of a single interaction is found from `albedo` as described
+    /// by Chiang et al. in "Practical and Controllable Subsurface Scattering
+    /// for Production Path Tracing" (2016).
+    pub fn new(albedo: Color, mean_free_path: Color, refraction_idx: f64) -> Subsurface {
+        let single_scattering_albedo = |albedo: f64| {
+            let a = albedo.clamp(0.0, 1.0);
+            1.0 - (a * (-5.09406 + a * (2.61188 - a * 4.31805))).exp()
+        };
+        let sigma_t = |albedo: f64, mean_free_path: f64| {
+            let a = albedo.clamp(0.0, 1.0);
+            let s = 1.9 - a + 3.5 * (a - 0.8) * (a - 0.8);
+            1.0 / (mean_free_path * s).max(1e-6)
+        };
+
+        Subsurface {
+            refraction_idx,
+            medium: Medium {
+                sigma_t: color!(
+                    sigma_t(albedo.red, mean_free_path.red),
+                    sigma_t(albedo.green, mean_free_path.green),
+                    sigma_t(albedo.blue, mean_free_path.blue)
+                ),
+                albedo: color!(
+                    single_scattering_albedo(albedo.red),
+                    single_scattering_albedo(albedo.green),
+                    single_scattering_albedo(albedo.blue)
+                ),
+            },
+        }
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, ray_in: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
+        let refraction_ratio = if hit.front_face {
+            1.0 / self.refraction_idx
+        } else {
+            self.refraction_idx
+        };
+
+        // Reflect or refract depending on the Fresnel reflectance, which is
+        // 1.0 when light can't get out of the object
+        let cos_theta = hit.shading_normal.dot(-ray_in.direction).min(1.0);
+        let reflectance = fresnel_dielectric(cos_theta, 1.0 / refraction_ratio);
+        let direction = if random_num() < reflectance {
+            ray_in.direction.reflect(hit.shading_normal)
+        } else {
+            ray_in
+                .direction
+                .refract(hit.shading_normal, refraction_ratio)
+        };
+        if !hit.same_side(direction) {
+            return None;
+        }
+        Some((Ray::new(hit.point, direction), color!(1.0, 1.0, 1.0)))
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        Some(self.medium)
+    }
+}
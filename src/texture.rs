@@ -0,0 +1,225 @@
+use std::{io, path::Path};
+
+use crate::{color, color::Color, vector3::Vec3};
+
+/// Trait for colors that change along the surface of an object
+pub trait Texture {
+    /// Return the color at the point `point`, whose surface coordinates are
+    /// `(u, v)`
+    fn value(&self, u: f64, v: f64, point: Vec3) -> Color;
+}
+
+/// Texture with the same color everywhere
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _point: Vec3) -> Color {
+        self.color
+    }
+}
+
+/// Checkerboard pattern in surface coordinates, with `squares` squares along
+/// each coordinate
+pub struct Checker {
+    even: Color,
+    odd: Color,
+    squares: f64,
+}
+
+impl Checker {
+    pub fn new(even: Color, odd: Color, squares: u32) -> Checker {
+        Checker {
+            even,
+            odd,
+            squares: squares as f64,
+        }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, _point: Vec3) -> Color {
+        let square = (u * self.squares).floor() as i64 + (v * self.squares).floor() as i64;
+        if square % 2 == 0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+/// Texture that maps an image to the surface coordinates, with `(0, 0)` at
+/// the bottom left corner of the image and `(1, 1)` at the top right one
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    /// Colors of the pixels, stored row by row starting with the top one
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    /// Load a PPM image (either plain or binary) from a file. Pixel values
+    /// are converted from gamma 2, the same gamma with which images are
+    /// rendered, to linear colors.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<ImageTexture> {
+        ImageTexture::from_ppm(&std::fs::read(path)?)
+    }
+
+    /// Load a PPM image whose pixel values are used as they are, like the
+    /// ones of normal and height maps, which aren't colors
+    pub fn load_linear(path: impl AsRef<Path>) -> io::Result<ImageTexture> {
+        ImageTexture::from_ppm_linear(&std::fs::read(path)?)
+    }
+
+    /// Decode the contents of a PPM image
+    pub fn from_ppm(data: &[u8]) -> io::Result<ImageTexture> {
+        ImageTexture::decode(data, true)
+    }
+
+    /// Decode the contents of a PPM image without converting its pixel
+    /// values from gamma 2
+    pub fn from_ppm_linear(data: &[u8]) -> io::Result<ImageTexture> {
+        ImageTexture::decode(data, false)
+    }
+
+    fn decode(data: &[u8], gamma_encoded: bool) -> io::Result<ImageTexture> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        // The header is made of 4 whitespace separated tokens, and comments
+        // go from '#' to the end of the line
+        let mut position = 0;
+        let mut next_token = || {
+            loop {
+                match data.get(position) {
+                    Some(b'#') => {
+                        while data.get(position).is_some_and(|&byte| byte != b'\n') {
+                            position += 1;
+                        }
+                    }
+                    Some(byte) if byte.is_ascii_whitespace() => position += 1,
+                    _ => break,
+                }
+            }
+            let start = position;
+            while data
+                .get(position)
+                .is_some_and(|byte| !byte.is_ascii_whitespace())
+            {
+                position += 1;
+            }
+            std::str::from_utf8(&data[start..position]).unwrap_or("")
+        };
+
+        let magic = next_token();
+        let mut number = || {
+            next_token()
+                .parse::<usize>()
+                .map_err(|_| invalid("invalid number in PPM image"))
+        };
+        let (width, height, max_value) = (number()?, number()?, number()?);
+        if width == 0 || height == 0 || max_value == 0 || max_value > 255 {
+            return Err(invalid("unsupported PPM image size or depth"));
+        }
+        let value_count = width
+            .checked_mul(height)
+            .and_then(|pixel_count| pixel_count.checked_mul(3))
+            .ok_or_else(|| invalid("unsupported PPM image size or depth"))?;
+
+        let values: Vec<usize> = match magic {
+            "P3" => {
+                // Every value takes at least one byte, so the header can't
+                // ask for more values than there are bytes in the image
+                if value_count > data.len() {
+                    return Err(invalid("truncated PPM image"));
+                }
+                (0..value_count)
+                    .map(|_| number())
+                    .collect::<io::Result<_>>()?
+            }
+            "P6" => {
+                // A single whitespace byte separates the header from the pixels
+                let start = position + 1;
+                let end = start
+                    .checked_add(value_count)
+                    .ok_or_else(|| invalid("truncated PPM image"))?;
+                data.get(start..end)
+                    .ok_or_else(|| invalid("truncated PPM image"))?
+                    .iter()
+                    .map(|&byte| byte as usize)
+                    .collect()
+            }
+            _ => return Err(invalid("not a PPM image")),
+        };
+
+        let to_linear = |value: usize| {
+            let value = value as f64 / max_value as f64;
+            if gamma_encoded {
+                value * value
+            } else {
+                value
+            }
+        };
+        let pixels = values
+            .chunks(3)
+            .map(|rgb| color!(to_linear(rgb[0]), to_linear(rgb[1]), to_linear(rgb[2])))
+            .collect();
+        Ok(ImageTexture {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _point: Vec3) -> Color {
+        let x = ((u.clamp(0.0, 1.0) * self.width as f64) as usize).min(self.width - 1);
+        let y = (((1.0 - v.clamp(0.0, 1.0)) * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_plain_ppm() {
+        let texture =
+            ImageTexture::from_ppm_linear(b"P3\n# comment\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+        let left = texture.value(0.25, 0.5, Vec3::zero());
+        let right = texture.value(0.75, 0.5, Vec3::zero());
+        assert_eq!((left.red, left.blue), (1.0, 0.0));
+        assert_eq!((right.red, right.blue), (0.0, 1.0));
+    }
+
+    #[test]
+    fn rejects_huge_binary_image() {
+        let header = format!("P6 {} {} 255\n", usize::MAX / 2, 3);
+        let err = ImageTexture::from_ppm(header.as_bytes()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_huge_plain_image() {
+        let err = ImageTexture::from_ppm(b"P3 100000 100000 255\n0 0 0\n")
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_truncated_binary_image() {
+        let err = ImageTexture::from_ppm(b"P6 2 2 255\n\x00\x00\x00")
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{color, color::Color, complex::Complex, rtweekend::PI};
+
+/// Range of wavelengths in nanometers covered by the red, green and blue
+/// channels
+const CHANNEL_BANDS: [(f64, f64); 3] = [(580.0, 700.0), (490.0, 580.0), (400.0, 490.0)];
+
+/// Number of wavelengths at which the reflectance of each channel is
+/// averaged. Without this, thick films would show the interference colors
+/// of three single wavelengths, which look nothing like real ones.
+const SAMPLES_PER_CHANNEL: usize = 8;
+
+/// Thin transparent film over the surface of a material, like soap or oil.
+/// Light reflected by the top of the film and light reflected by the
+/// surface below it interfere with each other, which makes some colors
+/// brighter and others darker depending on the thickness of the film and the
+/// angle from which it is seen.
+#[derive(Clone, Copy)]
+pub struct ThinFilm {
+    /// Thickness of the film in nanometers
+    thickness: f64,
+    refraction_idx: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f64, refraction_idx: f64) -> ThinFilm {
+        ThinFilm {
+            thickness: thickness.max(0.0),
+            refraction_idx,
+        }
+    }
+
+    /// Return the reflectance of each color channel, for light coming from a
+    /// medium with refraction index `outside_idx` at an angle with the normal
+    /// whose cosine is `cos_theta`. `substrate_idx` is the (complex, for
+    /// conductors) refraction index of the material below the film for each
+    /// channel.
+    pub fn reflectance(
+        &self,
+        cos_theta: f64,
+        outside_idx: f64,
+        substrate_idx: [Complex; 3],
+    ) -> Color {
+        let channel = |i: usize| {
+            let (min, max) = CHANNEL_BANDS[i];
+            let step = (max - min) / SAMPLES_PER_CHANNEL as f64;
+            (0..SAMPLES_PER_CHANNEL)
+                .map(|j| {
+                    let wavelength = min + (j as f64 + 0.5) * step;
+                    self.airy_reflectance(cos_theta, outside_idx, substrate_idx[i], wavelength)
+                })
+                .sum::<f64>()
+                / SAMPLES_PER_CHANNEL as f64
+        };
+        color!(channel(0), channel(1), channel(2))
+    }
+
+    /// Return the reflectance for light of a single wavelength, adding up
+    /// the waves reflected by every number of bounces inside the film (the
+    /// Airy summation)
+    fn airy_reflectance(
+        &self,
+        cos_theta: f64,
+        outside_idx: f64,
+        substrate_idx: Complex,
+        wavelength: f64,
+    ) -> f64 {
+        let cos_theta = cos_theta.clamp(0.0, 1.0);
+        let n0 = Complex::from(outside_idx);
+        let n1 = Complex::from(self.refraction_idx);
+        let n2 = substrate_idx;
+
+        // Cosines of the angles inside the film and the substrate, given by
+        // Snell's law. They are complex when light can't go through.
+        let sin_theta = Complex::from(outside_idx * (1.0 - cos_theta * cos_theta).sqrt());
+        let one = Complex::from(1.0);
+        let cos0 = Complex::from(cos_theta);
+        let cos1 = (one - (sin_theta / n1) * (sin_theta / n1)).sqrt();
+        let cos2 = (one - (sin_theta / n2) * (sin_theta / n2)).sqrt();
+
+        // Difference in phase between the light reflected at the top of the
+        // film and the light that goes through the film and back
+        let phase = n1 * cos1 * (4.0 * PI * self.thickness / wavelength);
+        let phase_shift = Complex::new(-phase.im, phase.re).exp();
+
+        let airy = |r01: Complex, r12: Complex| {
+            let r = (r01 + r12 * phase_shift) / (one + r01 * r12 * phase_shift);
+            r.norm_squared()
+        };
+        let s_polarized = airy(fresnel_s(n0, cos0, n1, cos1), fresnel_s(n1, cos1, n2, cos2));
+        let p_polarized = airy(fresnel_p(n0, cos0, n1, cos1), fresnel_p(n1, cos1, n2, cos2));
+        (0.5 * (s_polarized + p_polarized)).clamp(0.0, 1.0)
+    }
+}
+
+/// Fresnel amplitude coefficient of s-polarized light reflected at the
+/// interface between media `i` and `j`
+fn fresnel_s(n_i: Complex, cos_i: Complex, n_j: Complex, cos_j: Complex) -> Complex {
+    (n_i * cos_i - n_j * cos_j) / (n_i * cos_i + n_j * cos_j)
+}
+
+/// Fresnel amplitude coefficient of p-polarized light reflected at the
+/// interface between media `i` and `j`
+fn fresnel_p(n_i: Complex, cos_i: Complex, n_j: Complex, cos_j: Complex) -> Complex {
+    (n_j * cos_i - n_i * cos_j) / (n_j * cos_i + n_i * cos_j)
+}
@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Range, Sub, SubAssign};
 
-use crate::rtweekend::{random_num, random_num_in_range};
+use crate::rtweekend::{random_num, random_num_in_range, PI};
 
 /// Struct that can either represent a 3D vector or a 3D point.
 #[derive(Clone, Copy)]
@@ -82,6 +82,18 @@ impl Vec3 {
         }
     }
 
+    /// Return a random unit vector in the hemisphere around the Z axis
+    /// (z >= 0.0), where directions close to the Z axis are more likely
+    /// to be chosen. The probability of a direction is cos(θ) / π, θ
+    /// being the angle between the direction and the Z axis.
+    pub fn random_cosine_direction() -> Vec3 {
+        let r1 = random_num();
+        let r2 = random_num();
+        let phi = 2.0 * PI * r1;
+        let r = r2.sqrt();
+        vec3!(r * phi.cos(), r * phi.sin(), (1.0 - r2).sqrt())
+    }
+
     /// Return the squared length of this vector
     pub fn length_squared(self) -> f64 {
         self.x * self.x + self.y * self.y + self.z * self.z